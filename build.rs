@@ -1,9 +1,19 @@
+#[cfg(feature = "jvm")]
 use j4rs::{JvmBuilder, MavenArtifact, MavenArtifactRepo, MavenSettings};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Only rerun when build.rs changes, saves a lot of time
     println!("cargo:rerun-if-changed=build.rs");
 
+    #[cfg(feature = "jvm")]
+    deploy_artifacts()?;
+
+    Ok(())
+}
+
+/// Download the jepsen and elle jars for the JVM
+#[cfg(feature = "jvm")]
+fn deploy_artifacts() -> Result<(), Box<dyn std::error::Error>> {
     let jvm = JvmBuilder::new()
         .with_maven_settings(MavenSettings::new(vec![
             MavenArtifactRepo::from("maven_central:https://repo1.maven.org/maven2"),
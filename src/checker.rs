@@ -1,7 +1,608 @@
-use j4rs::{errors::Result, Instance};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    path::Path,
+};
+
+#[cfg(feature = "jvm")]
+use j4rs::{
+    errors::{J4RsError, Result},
+    InvocationArg,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    history::{HistoryType, Process, SerializableHistoryList},
+    op::Op,
+};
+#[cfg(feature = "jvm")]
+use crate::{
+    parse_edn, read_edn,
+    utils::{clj_to_string, FromSerde, ToDe},
+    with_jvm, ClojureValue, IFn,
+};
 
 /// Checker
+#[cfg(feature = "jvm")]
 pub trait Checker {
     /// The check function, returns a map like `{:valid? true}`
-    fn check(history: Instance) -> Result<Instance>;
+    fn check(history: ClojureValue) -> Result<ClojureValue>;
+}
+
+/// The `:valid?` field of a check result, which is either a bool or
+/// `:unknown`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "RawValidType", into = "RawValidType")]
+pub enum ValidType {
+    True,
+    False,
+    Unknown,
+}
+
+impl ValidType {
+    /// `Some(true)` for `True`, `Some(false)` for `False` and `None` for
+    /// `Unknown`
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ValidType::True => Some(true),
+            ValidType::False => Some(false),
+            ValidType::Unknown => None,
+        }
+    }
+}
+
+/// Temp enum for [`ValidType`] ser/de
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum RawValidType {
+    Bool(bool),
+    Str(String),
+}
+
+impl TryFrom<RawValidType> for ValidType {
+    type Error = String;
+
+    /// Only `unknown` (or `:unknown`) is accepted besides bools, so that a
+    /// corrupted value is not mistaken for an unknown result.
+    fn try_from(raw: RawValidType) -> std::result::Result<Self, Self::Error> {
+        match raw {
+            RawValidType::Bool(true) => Ok(ValidType::True),
+            RawValidType::Bool(false) => Ok(ValidType::False),
+            RawValidType::Str(s) if s.trim_start_matches(':') == "unknown" => {
+                Ok(ValidType::Unknown)
+            }
+            RawValidType::Str(s) => Err(format!("invalid `valid?` value `{s}`")),
+        }
+    }
+}
+
+impl From<ValidType> for RawValidType {
+    fn from(valid: ValidType) -> Self {
+        match valid {
+            ValidType::True => RawValidType::Bool(true),
+            ValidType::False => RawValidType::Bool(false),
+            ValidType::Unknown => RawValidType::Str("unknown".to_string()),
+        }
+    }
+}
+
+/// The summary written by an external Jepsen run, i.e. the json form of
+/// `results.edn`. Only `:valid?` and `:results` are typed, per-checker
+/// results are kept as raw json values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JepsenResults {
+    #[serde(rename = "valid?")]
+    pub valid: ValidType,
+    #[serde(default)]
+    pub results: BTreeMap<String, Value>,
+}
+
+impl JepsenResults {
+    /// Load the results from a json file
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Whether the run is valid, `false` if it is invalid or unknown
+    pub fn is_valid(&self) -> bool {
+        self.valid == ValidType::True
+    }
+
+    /// Whether the run is invalid
+    pub fn is_invalid(&self) -> bool {
+        self.valid == ValidType::False
+    }
+
+    /// Whether the validity of the run is unknown
+    pub fn is_unknown(&self) -> bool {
+        self.valid == ValidType::Unknown
+    }
+
+    /// The anomaly types reported by all checkers, deduplicated and in the
+    /// order of [`AnomalyType`].
+    /// Checkers without `anomaly-types` are skipped.
+    pub fn anomaly_types(&self) -> anyhow::Result<Vec<AnomalyType>> {
+        let mut types = BTreeSet::new();
+        for result in self.results.values() {
+            if let Some(found) = result.get("anomaly-types") {
+                types.extend(Vec::<AnomalyType>::deserialize(found)?);
+            }
+        }
+        Ok(types.into_iter().collect())
+    }
+}
+
+/// Define [`AnomalyType`] along with the names of its known variants
+macro_rules! anomaly_types {
+    ($($variant:ident => $name:literal,)*) => {
+        /// An anomaly reported by Elle in `:anomaly-types`. Serialized as its
+        /// name, e.g. `"G1c"`; `":G1c"` is accepted too. Names Elle added
+        /// after this list are kept in [`AnomalyType::Other`].
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(from = "String", into = "String")]
+        pub enum AnomalyType {
+            $($variant,)*
+            Other(String),
+        }
+
+        impl AnomalyType {
+            /// The names of all known anomaly types
+            const NAMES: &'static [&'static str] = &[$($name,)*];
+
+            /// The name Elle uses for the anomaly, e.g. `G1c`
+            pub fn as_str(&self) -> &str {
+                match self {
+                    $(AnomalyType::$variant => $name,)*
+                    AnomalyType::Other(name) => name,
+                }
+            }
+        }
+
+        impl From<String> for AnomalyType {
+            fn from(name: String) -> Self {
+                match name.strip_prefix(':').unwrap_or(&name) {
+                    $($name => AnomalyType::$variant,)*
+                    other => AnomalyType::Other(other.to_string()),
+                }
+            }
+        }
+    };
+}
+
+anomaly_types! {
+    G0 => "G0",
+    G0Process => "G0-process",
+    G0Realtime => "G0-realtime",
+    G1a => "G1a",
+    G1b => "G1b",
+    G1c => "G1c",
+    G1cProcess => "G1c-process",
+    G1cRealtime => "G1c-realtime",
+    GSingle => "G-single",
+    GSingleProcess => "G-single-process",
+    GSingleRealtime => "G-single-realtime",
+    GSingleItem => "G-single-item",
+    GSingleItemProcess => "G-single-item-process",
+    GSingleItemRealtime => "G-single-item-realtime",
+    GNonadjacent => "G-nonadjacent",
+    GNonadjacentProcess => "G-nonadjacent-process",
+    GNonadjacentRealtime => "G-nonadjacent-realtime",
+    GNonadjacentItem => "G-nonadjacent-item",
+    GNonadjacentItemProcess => "G-nonadjacent-item-process",
+    GNonadjacentItemRealtime => "G-nonadjacent-item-realtime",
+    G2Item => "G2-item",
+    G2ItemProcess => "G2-item-process",
+    G2ItemRealtime => "G2-item-realtime",
+    G2 => "G2",
+    G2Process => "G2-process",
+    G2Realtime => "G2-realtime",
+    CycleSearchTimeout => "cycle-search-timeout",
+    CyclicVersions => "cyclic-versions",
+    DirtyUpdate => "dirty-update",
+    DuplicateElements => "duplicate-elements",
+    EmptyTransactionGraph => "empty-transaction-graph",
+    FutureRead => "future-read",
+    IncompatibleOrder => "incompatible-order",
+    Internal => "internal",
+    LostUpdate => "lost-update",
+}
+
+impl From<AnomalyType> for String {
+    fn from(anomaly: AnomalyType) -> Self {
+        match anomaly {
+            AnomalyType::Other(name) => name,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for AnomalyType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Every anomaly name Elle can report in `:anomaly-types`, i.e. the names of
+/// the known [`AnomalyType`]s. Cycle anomalies also come in `-process` and
+/// `-realtime` variants.
+pub fn all_anomaly_types() -> &'static [&'static str] {
+    AnomalyType::NAMES
+}
+
+/// Check that every name in `anomalies` is in [`all_anomaly_types`], so that
+/// a misspelled name fails early instead of never matching. A leading `:` is
+/// allowed.
+pub fn validate_anomalies(anomalies: &[&str]) -> anyhow::Result<()> {
+    let unknown = anomalies
+        .iter()
+        .filter(|name| matches!(AnomalyType::from(name.to_string()), AnomalyType::Other(_)))
+        .map(|name| format!("`{name}`"))
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        anyhow::bail!("unknown anomaly types: {}", unknown.join(", "));
+    }
+    Ok(())
+}
+
+/// Clojure fn reading a json string into EDN as Jepsen writes it: keys are
+/// keywords, every `"valid?": "unknown"` becomes `:valid? :unknown`, Elle's
+/// `:anomaly-types` are keywords and its `:not`/`:also-not` are keyword sets.
+#[cfg(feature = "jvm")]
+const JSON_TO_RESULTS_EDN_FN: &str = r#"
+(require 'clojure.data.json 'clojure.walk)
+(fn [s]
+  (clojure.walk/postwalk
+    (fn [x]
+      (if (map? x)
+        (cond-> x
+          (= "unknown" (:valid? x)) (assoc :valid? :unknown)
+          (sequential? (:anomaly-types x)) (update :anomaly-types (partial mapv keyword))
+          (sequential? (:not x)) (update :not (comp set (partial map keyword)))
+          (sequential? (:also-not x)) (update :also-not (comp set (partial map keyword))))
+        x))
+    (clojure.data.json/read-str s :key-fn keyword)))
+"#;
+
+#[cfg(feature = "jvm")]
+impl JepsenResults {
+    /// Convert the results back to EDN for Jepsen's reporting, with keyword
+    /// keys and `:unknown` validity.
+    pub fn to_edn_instance(&self) -> anyhow::Result<ClojureValue> {
+        let json = serde_json::to_string(self)?;
+        with_jvm(|_| {
+            let to_edn = IFn::new(read_edn(JSON_TO_RESULTS_EDN_FN)?);
+            Ok(to_edn.invoke1(InvocationArg::try_from(json)?)?)
+        })
+    }
+
+    /// Convert the results to an EDN string, like `results.edn`
+    pub fn to_edn_string(&self) -> anyhow::Result<String> {
+        Ok(clj_to_string(self.to_edn_instance()?)?)
+    }
+}
+
+/// The parsed contents of a check output directory
+#[cfg(feature = "jvm")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnalysisArtifacts {
+    /// `results.edn`
+    pub results: JepsenResults,
+    /// `anomalies.edn`, the anomalies found by Elle keyed by type, if present
+    pub anomalies: Option<BTreeMap<String, Value>>,
+}
+
+/// Load `results.edn` and, if present, `anomalies.edn` from the check output
+/// directory at `path`.
+#[cfg(feature = "jvm")]
+pub fn load_analysis_dir(path: impl AsRef<Path>) -> anyhow::Result<AnalysisArtifacts> {
+    let path = path.as_ref();
+    let results = parse_edn(&fs::read_to_string(path.join("results.edn"))?)?.to_de()?;
+    let anomalies = path.join("anomalies.edn");
+    let anomalies = if anomalies.exists() {
+        Some(parse_edn(&fs::read_to_string(anomalies)?)?.to_de()?)
+    } else {
+        None
+    };
+    Ok(AnalysisArtifacts { results, anomalies })
+}
+
+/// A read that observed a smaller value than an earlier read of the same key
+/// in the same process.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonotonicReadViolation {
+    /// The index of the history item containing the read
+    pub index: u64,
+    pub process: Process,
+    pub key: u64,
+    /// The value observed by the earlier read
+    pub prev: u64,
+    /// The smaller value observed by this read
+    pub value: u64,
+}
+
+/// A lightweight Rust-side checker, verifying that within each process the
+/// reads of a key never go backwards. Only meaningful when the written
+/// values of each key are monotonic, see `Generator::monotonic_per_key`.
+pub struct MonotonicReadChecker;
+
+impl MonotonicReadChecker {
+    /// Scan the `:ok` items of the history, returns all violations found.
+    pub fn check_history(history: &SerializableHistoryList) -> Vec<MonotonicReadViolation> {
+        let mut last = HashMap::new();
+        let mut violations = vec![];
+        for item in history.iter().filter(|h| h.type_ == HistoryType::Ok) {
+            let mut reads = vec![];
+            collect_reads(&item.value, &mut reads);
+            for (key, value) in reads {
+                match last.insert((item.process, key), value) {
+                    Some(prev) if value < prev => {
+                        last.insert((item.process, key), prev);
+                        violations.push(MonotonicReadViolation {
+                            index: item.index,
+                            process: item.process,
+                            key,
+                            prev,
+                            value,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        violations
+    }
+}
+
+/// Collect the `(key, value)` of all reads that returned a value, in order.
+fn collect_reads(op: &Op, reads: &mut Vec<(u64, u64)>) {
+    match op {
+        Op::Read(key, Some(value)) => reads.push((*key, *value)),
+        Op::Read(_, None) | Op::Write(_, _) => {}
+        Op::Txn(ops) => ops.iter().for_each(|op| collect_reads(op, reads)),
+    }
+}
+
+#[cfg(feature = "jvm")]
+impl Checker for MonotonicReadChecker {
+    /// Returns a map like `{:valid? false, :violations [...]}`
+    fn check(history: ClojureValue) -> Result<ClojureValue> {
+        let history: SerializableHistoryList = history
+            .to_de()
+            .map_err(|e| J4RsError::GeneralError(e.to_string()))?;
+        let violations = Self::check_history(&history);
+        ClojureValue::from_ser(serde_json::json!({
+            "valid?": violations.is_empty(),
+            "violations": violations,
+        }))
+        .map_err(|e| J4RsError::GeneralError(e.to_string()))
+    }
+}
+
+/// Run the checker `C` over `history` on a dedicated thread, which attaches
+/// itself to the JVM on first use. This suits callers on their own thread
+/// pools, as the result is returned as plain json that is `Send`.
+#[cfg(feature = "jvm")]
+pub fn check_on_thread<C: Checker + 'static>(
+    history: SerializableHistoryList,
+) -> anyhow::Result<Value> {
+    std::thread::spawn(move || C::check(ClojureValue::from_ser(&history)?)?.to_de())
+        .join()
+        .map_err(|_| anyhow::anyhow!("checker thread panicked"))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::HistoryBuilder;
+
+    #[test]
+    fn test_all_anomaly_types() -> anyhow::Result<()> {
+        let all = all_anomaly_types();
+        assert!(all.contains(&"G1c"));
+        assert!(all.contains(&"G-single-realtime"));
+        assert!(!all.contains(&"g1-a"));
+        let unique = all.iter().collect::<std::collections::HashSet<_>>();
+        assert_eq!(unique.len(), all.len());
+
+        let res = JepsenResults::from_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/ex_results.json"
+        ))?;
+        let found = res.anomaly_types()?;
+        assert_eq!(found, vec![AnomalyType::G1c]);
+        assert!(found.iter().all(|t| all.contains(&t.as_str())), "{found:?}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_anomalies() {
+        validate_anomalies(&[]).unwrap();
+        validate_anomalies(&["G1c", ":G-single", "internal"]).unwrap();
+        validate_anomalies(all_anomaly_types()).unwrap();
+
+        let err = validate_anomalies(&["G1c", "g1-a", "G-singel"]).unwrap_err();
+        assert_eq!(err.to_string(), "unknown anomaly types: `g1-a`, `G-singel`");
+    }
+
+    #[test]
+    fn test_anomaly_type_serde() -> anyhow::Result<()> {
+        let types: Vec<AnomalyType> =
+            serde_json::from_str(r#"["G1c", ":G-single-item-realtime", "internal", "G42"]"#)?;
+        assert_eq!(
+            types,
+            vec![
+                AnomalyType::G1c,
+                AnomalyType::GSingleItemRealtime,
+                AnomalyType::Internal,
+                AnomalyType::Other("G42".to_string()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&types)?,
+            r#"["G1c","G-single-item-realtime","internal","G42"]"#
+        );
+        assert_eq!(
+            AnomalyType::EmptyTransactionGraph.to_string(),
+            "empty-transaction-graph"
+        );
+        for name in all_anomaly_types() {
+            let parsed = AnomalyType::from(name.to_string());
+            assert!(!matches!(parsed, AnomalyType::Other(_)), "{name}");
+            assert_eq!(parsed.as_str(), *name);
+        }
+
+        let res: JepsenResults = serde_json::from_value(serde_json::json!({
+            "valid?": false,
+            "results": {
+                "elle": {"valid?": false, "anomaly-types": ["G2-item", "G1a", "G42"]},
+                "list": {"valid?": false, "anomaly-types": ["G1a"]},
+                "perf": {"valid?": true},
+            },
+        }))?;
+        assert_eq!(
+            res.anomaly_types()?,
+            vec![
+                AnomalyType::G1a,
+                AnomalyType::G2Item,
+                AnomalyType::Other("G42".to_string()),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_jepsen_results() -> anyhow::Result<()> {
+        let res = JepsenResults::from_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/ex_results.json"
+        ))?;
+        assert_eq!(res.valid, ValidType::False);
+        assert_eq!(res.results.len(), 2);
+        assert_eq!(res.results["workload"]["valid?"], Value::Bool(false));
+        let perf: ValidType = serde_json::from_value(res.results["perf"]["valid?"].clone())?;
+        assert_eq!(perf, ValidType::Unknown);
+        Ok(())
+    }
+
+    #[cfg(feature = "jvm")]
+    #[test]
+    fn test_results_edn_round_trip() -> anyhow::Result<()> {
+        use crate::init_jvm;
+
+        init_jvm();
+        let res = JepsenResults::from_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/assets/ex_results.json"
+        ))?;
+        let edn = res.to_edn_string()?;
+        assert!(edn.contains(":valid? false"), "{edn}");
+        assert!(edn.contains(":valid? :unknown"), "{edn}");
+        assert!(edn.contains(":anomaly-types [:G1c]"), "{edn}");
+        assert!(edn.contains(":not #{:read-committed}"), "{edn}");
+        let expected = parse_edn(include_str!("../assets/ex_analysis/results.edn"))?;
+        let actual = res.to_edn_instance()?;
+        let eq = cljinvoke!("=", actual, expected)?;
+        assert_eq!(clj_to_string(eq)?, "true", "{edn}");
+
+        let back: JepsenResults = read_edn(&edn)?.to_de()?;
+        assert_eq!(back, res);
+        Ok(())
+    }
+
+    #[cfg(feature = "jvm")]
+    #[test]
+    fn test_load_analysis_dir() -> anyhow::Result<()> {
+        let res = load_analysis_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/ex_analysis"))?;
+        assert!(res.results.is_invalid());
+        let perf: ValidType =
+            serde_json::from_value(res.results.results["perf"]["valid?"].clone())?;
+        assert_eq!(perf, ValidType::Unknown);
+        assert_eq!(
+            res.results.results["workload"]["anomaly-types"],
+            serde_json::json!(["G1c"])
+        );
+
+        let anomalies = res.anomalies.unwrap();
+        assert_eq!(anomalies.keys().collect::<Vec<_>>(), vec!["G1c"]);
+        assert_eq!(anomalies["G1c"][0]["cycle"][1]["index"], Value::from(5));
+        assert_eq!(anomalies["G1c"][0]["steps"][0]["type"], Value::from("wr"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_valid_type_serde() -> anyhow::Result<()> {
+        for (json, valid) in [
+            ("true", ValidType::True),
+            ("false", ValidType::False),
+            (r#""unknown""#, ValidType::Unknown),
+            (r#"":unknown""#, ValidType::Unknown),
+        ] {
+            assert_eq!(serde_json::from_str::<ValidType>(json)?, valid);
+        }
+        assert_eq!(serde_json::to_string(&ValidType::Unknown)?, r#""unknown""#);
+        let err = serde_json::from_str::<ValidType>(r#""yes""#).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid `valid?` value `yes`"),
+            "{err}"
+        );
+        assert!(serde_json::from_str::<JepsenResults>(r#"{"valid?":"yes"}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_validity_accessors() {
+        let res = |valid| JepsenResults {
+            valid,
+            results: BTreeMap::new(),
+        };
+        assert_eq!(ValidType::True.as_bool(), Some(true));
+        assert_eq!(ValidType::False.as_bool(), Some(false));
+        assert_eq!(ValidType::Unknown.as_bool(), None);
+
+        let valid = res(ValidType::True);
+        assert!(valid.is_valid() && !valid.is_invalid() && !valid.is_unknown());
+        let invalid = res(ValidType::False);
+        assert!(!invalid.is_valid() && invalid.is_invalid() && !invalid.is_unknown());
+        let unknown = res(ValidType::Unknown);
+        assert!(!unknown.is_valid() && !unknown.is_invalid() && unknown.is_unknown());
+    }
+
+    #[cfg(feature = "jvm")]
+    #[test]
+    fn test_check_on_thread() -> anyhow::Result<()> {
+        let his = HistoryBuilder::new()
+            .ok(0, Op::Read(1, Some(2)))
+            .ok(0, Op::Read(1, Some(1)))
+            .build();
+        // A thread that never touched the JVM
+        let res = std::thread::spawn(move || check_on_thread::<MonotonicReadChecker>(his))
+            .join()
+            .unwrap()?;
+        assert_eq!(res["valid?"], Value::Bool(false));
+        assert_eq!(res["violations"].as_array().map(Vec::len), Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_monotonic_read_checker() {
+        let his = HistoryBuilder::new()
+            .ok(0, Op::Read(1, Some(2)))
+            .ok(1, Op::Read(1, Some(1)))
+            .ok(0, Op::Txn(vec![Op::Write(1, 3), Op::Read(2, Some(5))]))
+            .ok(0, Op::Txn(vec![Op::Read(1, Some(1)), Op::Read(2, None)]))
+            .ok(1, Op::Read(1, Some(3)))
+            .ok(0, Op::Read(1, Some(2)))
+            .build();
+        assert_eq!(
+            MonotonicReadChecker::check_history(&his),
+            vec![MonotonicReadViolation {
+                index: 3,
+                process: Process::Client(0),
+                key: 1,
+                prev: 2,
+                value: 1,
+            }]
+        );
+    }
 }
@@ -0,0 +1,389 @@
+//! The JVM and Clojure interop layer.
+
+use std::{borrow::Borrow, cell::OnceCell, marker::PhantomData, thread::LocalKey};
+
+use j4rs::{errors::J4RsError, Instance, InvocationArg, Jvm, JvmBuilder};
+
+thread_local! {
+    static JVM: OnceCell<Jvm> = const { OnceCell::new() };
+    static PARSE_EDN: OnceCell<IFn> = const { OnceCell::new() };
+}
+
+pub fn init_jvm() {
+    JVM.with(|cell| {
+        cell.get_or_init(|| {
+            let _jvm = JvmBuilder::new().build().expect("Failed to initialize JVM");
+            Jvm::attach_thread().expect("Failed to attach JVM to thread")
+        });
+    })
+}
+
+pub fn with_jvm<F, R>(f: F) -> R
+where
+    F: FnOnce(&Jvm) -> R,
+{
+    JVM.with(|cell| {
+        let jvm = cell.get_or_init(|| {
+            let _jvm = JvmBuilder::new().build().expect("Failed to initialize JVM");
+            Jvm::attach_thread().expect("Failed to attach JVM to thread")
+        });
+        f(jvm)
+    })
+}
+
+/// Evaluate `arg` as clojure code, returning the value of the last form
+pub fn read_edn(arg: &str) -> j4rs::errors::Result<ClojureValue> {
+    with_jvm(|_| cljinvoke!("load-string", arg))
+}
+
+/// Clojure fn reading an EDN string as data. Tagged literals, e.g. the
+/// `#jepsen.history.Op{...}` records in Elle's output, read as their value.
+const PARSE_EDN_FN: &str = r#"
+(require 'clojure.edn)
+(fn [s] (clojure.edn/read-string {:default (fn [_tag value] value)} s))
+"#;
+
+/// Read `s` as EDN data without evaluating it, unlike [`read_edn`]. Use it
+/// for files written by Jepsen or Elle, which pprint seqs as lists like
+/// `(:G1c)`.
+pub fn parse_edn(s: &str) -> j4rs::errors::Result<ClojureValue> {
+    with_cached_fn(&PARSE_EDN, PARSE_EDN_FN, |parse| {
+        parse.invoke1(InvocationArg::try_from(s)?)
+    })
+}
+
+/// Call the Clojure fn cached in `key`, compiling it from `src` on first use
+/// in this thread.
+pub(crate) fn with_cached_fn<R, E: From<J4RsError>>(
+    key: &'static LocalKey<OnceCell<IFn>>,
+    src: &str,
+    f: impl FnOnce(&IFn) -> Result<R, E>,
+) -> Result<R, E> {
+    key.with(|cell| {
+        if cell.get().is_none() {
+            let _ = cell.set(IFn::new(read_edn(src)?));
+        }
+        f(cell.get().expect("initialized above"))
+    })
+}
+
+/// Create a clojure keyword, e.g. `keyword("foo")` is `:foo`
+pub fn keyword(name: &str) -> j4rs::errors::Result<ClojureValue> {
+    with_jvm(|_| cljinvoke!("keyword", name))
+}
+
+/// Create a clojure symbol, e.g. `symbol("foo")` is `'foo`
+pub fn symbol(name: &str) -> j4rs::errors::Result<ClojureValue> {
+    with_jvm(|_| cljinvoke!("symbol", name))
+}
+
+/// Set the level of the logback root logger used by Jepsen and Elle, e.g.
+/// `"WARN"` to quiet Jepsen's info logs. Unknown levels fall back to
+/// `DEBUG`, as `ch.qos.logback.classic.Level/toLevel` does.
+pub fn init_jvm_logging(level: &str) -> j4rs::errors::Result<()> {
+    with_jvm(|jvm| {
+        let logger = jvm.invoke_static(
+            "org.slf4j.LoggerFactory",
+            "getLogger",
+            &[InvocationArg::try_from("ROOT")?],
+        )?;
+        let logger = jvm.cast(&logger, "ch.qos.logback.classic.Logger")?;
+        let level = jvm.invoke_static(
+            "ch.qos.logback.classic.Level",
+            "toLevel",
+            &[InvocationArg::try_from(level)?],
+        )?;
+        jvm.invoke(&logger, "setLevel", &[InvocationArg::from(level)])?;
+        Ok(())
+    })
+}
+
+/// Get the major version and the full `java.version` property of the JVM,
+/// e.g. `(21, "21.0.2")`. Legacy `1.x` versions report `x` as the major.
+pub fn jvm_version() -> j4rs::errors::Result<(u32, String)> {
+    with_jvm(|jvm| {
+        let version = jvm.invoke_static(
+            "java.lang.System",
+            "getProperty",
+            &[InvocationArg::try_from("java.version")?],
+        )?;
+        let version: String = jvm.to_rust(version)?;
+        let major = parse_java_major(&version)
+            .ok_or_else(|| J4RsError::ParseError(format!("invalid java version `{version}`")))?;
+        Ok((major, version))
+    })
+}
+
+/// Parse the major version out of a `java.version` property
+fn parse_java_major(version: &str) -> Option<u32> {
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    match parts.next()?.parse().ok()? {
+        1 => parts.next()?.parse().ok(),
+        major => Some(major),
+    }
+}
+
+/// Fail with a clear message if the JVM is older than Java `major`. Call it
+/// early in setup, since Jepsen fails cryptically on an old JDK, see
+/// <https://github.com/jepsen-io/jepsen/issues/585>.
+pub fn require_java_at_least(major: u32) -> j4rs::errors::Result<()> {
+    let (actual, version) = jvm_version()?;
+    if actual < major {
+        return Err(J4RsError::GeneralError(format!(
+            "java {major} or newer is required, but the JVM is java {version}"
+        )));
+    }
+    Ok(())
+}
+
+pub(crate) fn invoke_clojure_java_api(
+    method_name: &str,
+    inv_args: &[impl Borrow<InvocationArg>],
+) -> j4rs::errors::Result<Instance> {
+    with_jvm(|jvm| {
+        jvm.invoke(
+            &with_jvm(|jvm| jvm.static_class("clojure.java.api.Clojure"))?,
+            method_name,
+            inv_args,
+        )
+    })
+}
+
+/// A clojure value. j4rs `Instance`s belong to the JVM attached to the
+/// thread that created them, while the JVM here is thread local, so this
+/// wrapper is `!Send` to catch cross-thread use at compile time.
+///
+/// Every clojure call returns one: [`read_edn`], [`keyword`], [`symbol`],
+/// the [`IFn`] invoke methods and `cljinvoke!`, `cljeval!` and `nsinvoke!`.
+/// It can be passed anywhere an `InvocationArg` is expected.
+/// [`ClojureValue::into_instance`] is the escape hatch for APIs taking a raw
+/// `Instance`, which is `Send` again.
+///
+/// ```compile_fail
+/// use jepsen_rs::read_edn;
+/// let value = read_edn(":foo").unwrap();
+/// std::thread::spawn(move || drop(value));
+/// ```
+///
+/// ```compile_fail
+/// use jepsen_rs::cljeval;
+/// let value = cljeval!((+ 1 2)).unwrap();
+/// std::thread::spawn(move || drop(value));
+/// ```
+pub struct ClojureValue {
+    inner: Instance,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl ClojureValue {
+    pub fn new(inner: Instance) -> Self {
+        Self {
+            inner,
+            _not_send: PhantomData,
+        }
+    }
+
+    pub fn as_instance(&self) -> &Instance {
+        &self.inner
+    }
+
+    /// Clone the reference to the same JVM object
+    pub fn try_clone(&self) -> j4rs::errors::Result<Self> {
+        with_jvm(|jvm| jvm.clone_instance(&self.inner)).map(Self::new)
+    }
+
+    /// Unwrap the raw `Instance`, giving up the `!Send` guarantee
+    pub fn into_instance(self) -> Instance {
+        self.inner
+    }
+}
+
+impl From<Instance> for ClojureValue {
+    fn from(inner: Instance) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl From<ClojureValue> for InvocationArg {
+    fn from(value: ClojureValue) -> Self {
+        InvocationArg::from(value.inner)
+    }
+}
+
+pub struct IFn {
+    inner: ClojureValue,
+}
+
+impl IFn {
+    pub fn new(inner: impl Into<ClojureValue>) -> Self {
+        Self {
+            inner: inner.into(),
+        }
+    }
+
+    pub fn invoke0(&self) -> j4rs::errors::Result<ClojureValue> {
+        self.invoke(&[] as &[InvocationArg])
+    }
+
+    pub fn invoke1(&self, arg: impl Into<InvocationArg>) -> j4rs::errors::Result<ClojureValue> {
+        self.invoke(&[arg.into()])
+    }
+
+    pub fn invoke(
+        &self,
+        args: &[impl Borrow<InvocationArg>],
+    ) -> j4rs::errors::Result<ClojureValue> {
+        with_jvm(|jvm| jvm.invoke(self.inner.as_instance(), "invoke", args)).map(ClojureValue::new)
+    }
+
+    pub fn get_cls(&self, name: &str) -> j4rs::errors::Result<ClojureValue> {
+        with_jvm(|jvm| jvm.field(self.inner.as_instance(), name)).map(ClojureValue::new)
+    }
+
+    pub fn into_inner(self) -> Instance {
+        self.inner.into_instance()
+    }
+}
+
+/// Clojure Namespace. A namespace should be created by `CljCore::require`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CljNs {
+    ns: String,
+}
+
+impl CljNs {
+    pub fn var(&self, name: &str) -> j4rs::errors::Result<IFn> {
+        Self::var_inner(&self.ns, name)
+    }
+
+    fn var_inner(ns: &str, name: &str) -> j4rs::errors::Result<IFn> {
+        Ok(IFn::new(cljinvoke_java_api!("var", ns, name)?))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CljCore {
+    ns: &'static str,
+}
+
+pub static CLOJURE: CljCore = CljCore { ns: "clojure.core" };
+
+impl CljCore {
+    pub fn require(&self, ns: &str) -> j4rs::errors::Result<CljNs> {
+        CljNs::var_inner(self.ns, "require")?.invoke1(cljinvoke_java_api!("read", ns)?)?;
+        Ok(CljNs { ns: ns.to_string() })
+    }
+
+    pub fn var(&self, name: &str) -> j4rs::errors::Result<IFn> {
+        CljNs::var_inner(self.ns, name)
+    }
+}
+
+impl Default for CljCore {
+    fn default() -> Self {
+        CLOJURE.clone()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::{clj_to_string, pre_serialize, print, print_clj};
+
+    #[test]
+    fn test_elle_check() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let r = CLOJURE.require("elle.rw-register")?;
+        let h = CLOJURE.require("jepsen.history")?;
+        let history = read_edn(include_str!("../assets/ex_history.edn"))?;
+        let history = nsinvoke!(h, "history", history)?;
+        let res = nsinvoke!(r, "check", history)?;
+        print_clj(res);
+        Ok(())
+    }
+
+    #[test]
+    fn test_elle_gen() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let r = CLOJURE.require("elle.rw-register")?;
+        let gen = nsinvoke!(r, "gen")?;
+        let take = cljinvoke!("take", 5, gen)?;
+        let value = pre_serialize(take)?;
+        print_clj(value);
+        Ok(())
+    }
+
+    #[test]
+    fn elle_gen_analysis() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let r = CLOJURE.require("elle.rw-register")?;
+        let h = CLOJURE.require("jepsen.history")?;
+        let gen = r.var("gen")?.invoke0()?;
+        let history = cljinvoke!("take", 10, gen)?;
+        let res = nsinvoke!(r, "check", nsinvoke!(h, "history", history)?)?;
+        print(res.into_instance());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_java_major() {
+        assert_eq!(parse_java_major("21.0.2"), Some(21));
+        assert_eq!(parse_java_major("17"), Some(17));
+        assert_eq!(parse_java_major("22-ea"), Some(22));
+        assert_eq!(parse_java_major("1.8.0_392"), Some(8));
+        assert_eq!(parse_java_major("abc"), None);
+    }
+
+    #[test]
+    fn test_jvm_version() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let (major, version) = jvm_version()?;
+        assert!((8..100).contains(&major), "{version}");
+        assert!(version.starts_with(&major.to_string()) || version.starts_with("1."));
+        require_java_at_least(major)?;
+        assert!(require_java_at_least(major + 1).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_init_jvm_logging() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        init_jvm_logging("WARN")?;
+        let info = cljeval!((.isInfoEnabled (org.slf4j.LoggerFactory/getLogger "jepsen.core")))?;
+        assert_eq!(clj_to_string(info)?, "false");
+        init_jvm_logging("INFO")?;
+        let info = cljeval!((.isInfoEnabled (org.slf4j.LoggerFactory/getLogger "jepsen.core")))?;
+        assert_eq!(clj_to_string(info)?, "true");
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyword_and_symbol() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        assert_eq!(clj_to_string(keyword("foo")?)?, ":foo");
+        assert_eq!(clj_to_string(symbol("foo")?)?, "foo");
+        let eq = cljinvoke!("=", keyword("foo")?, read_edn(":foo")?)?;
+        assert_eq!(clj_to_string(eq)?, "true");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_edn() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let list = parse_edn("{:anomaly-types (:G1c)}")?;
+        assert_eq!(clj_to_string(list)?, "{:anomaly-types (:G1c)}");
+        let op = parse_edn("#jepsen.history.Op{:index 1, :f :txn}")?;
+        assert_eq!(clj_to_string(op)?, "{:index 1, :f :txn}");
+        Ok(())
+    }
+
+    /// We can define a function in namespace, and call it later.
+    #[test]
+    fn test_defn_in_ns() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let _x = cljeval!((defn test [] (str "hello" "world")))?;
+        let y = cljeval!((test))?;
+        print_clj(y);
+        Ok(())
+    }
+}
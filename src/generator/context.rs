@@ -1,12 +1,16 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     sync::{Arc, Mutex},
 };
 
 use madsim::{runtime::NodeHandle, time};
 
 use super::GeneratorId;
-use crate::{generator::RawGenerator, history::SerializableHistoryList};
+use crate::{
+    generator::RawGenerator,
+    history::{ErrorType, HistorySink, HistoryType, Process, SerializableHistoryList},
+    op::Op,
+};
 
 /// The global context
 #[non_exhaustive]
@@ -19,6 +23,13 @@ pub struct Global {
     pub start_time: time::Instant,
     /// The history list
     pub history: Mutex<SerializableHistoryList>,
+    /// Where [`Global::record_invoke`] and [`Global::record_result`] write
+    /// to, `None` records into `history`.
+    sink: Option<Mutex<Box<dyn HistorySink>>>,
+    /// The ids below the largest allocated one that are not in the thread
+    /// pool, so that the smallest free id is found without scanning the pool.
+    /// Always locked after `thread_pool`.
+    free_ids: Mutex<BTreeSet<GeneratorId>>,
 }
 
 impl Global {
@@ -29,42 +40,117 @@ impl Global {
             gen,
             start_time: time::Instant::now(),
             history: Mutex::new(SerializableHistoryList::default()),
+            sink: None,
+            free_ids: Mutex::new(BTreeSet::new()),
+        }
+    }
+    /// Set the start time, all history timestamps are relative to it
+    pub fn with_start_time(mut self, start_time: time::Instant) -> Self {
+        self.start_time = start_time;
+        self
+    }
+    /// Record the history into `sink` instead of the in-memory `history`
+    pub fn with_history_sink(mut self, sink: Box<dyn HistorySink>) -> Self {
+        self.sink = Some(Mutex::new(sink));
+        self
+    }
+    /// Record an invoke into the history sink, see [`Global::with_history_sink`]
+    pub fn record_invoke(
+        self: &Arc<Self>,
+        process: impl Into<Process>,
+        value: Op,
+        trace_id: Option<u64>,
+    ) {
+        let process = process.into();
+        match &self.sink {
+            Some(sink) => sink
+                .lock()
+                .expect("Failed to lock history sink")
+                .record_invoke(self, process, value, trace_id),
+            None => self
+                .history
+                .lock()
+                .expect("Failed to lock history")
+                .record_invoke(self, process, value, trace_id),
         }
     }
+    /// Record a result into the history sink, see [`Global::with_history_sink`]
+    pub fn record_result(
+        self: &Arc<Self>,
+        process: impl Into<Process>,
+        result_type: HistoryType,
+        value: Op,
+        error: Option<ErrorType>,
+        trace_id: Option<u64>,
+    ) {
+        let process = process.into();
+        match &self.sink {
+            Some(sink) => sink
+                .lock()
+                .expect("Failed to lock history sink")
+                .record_result(self, process, result_type, value, error, trace_id),
+            None => self
+                .history
+                .lock()
+                .expect("Failed to lock history")
+                .record_result(self, process, result_type, value, error, trace_id),
+        }
+    }
+    /// Move the items of a worker-local `batch` into the shared history
+    /// under a single lock, instead of locking once per invoke and result.
+    /// The merged history is ordered by flush, so call
+    /// [`SerializableHistoryList::sort_by_time`] on it once all workers are
+    /// done.
+    pub fn flush_history(&self, batch: &mut SerializableHistoryList) {
+        self.history
+            .lock()
+            .expect("Failed to lock history")
+            .append(batch);
+    }
     /// Find the minimal usable id in the thread pool
     pub fn get_next_id(&self) -> GeneratorId {
         let pool = self.thread_pool.lock().expect("Failed to lock thread pool");
-        for (index, id) in pool.keys().enumerate() {
-            if index as u64 != *id {
-                return index as u64;
-            }
-        }
-        pool.len() as u64
+        let free_ids = self.free_ids.lock().expect("Failed to lock free ids");
+        free_ids
+            .first()
+            .copied()
+            .unwrap_or_else(|| next_unused(&pool))
     }
     /// Allocate a new generator
     pub fn alloc_new_generator(&self, handle: NodeHandle) -> GeneratorId {
-        let id = self.get_next_id();
-        self.thread_pool
-            .lock()
-            .expect("Failed to lock thread pool")
-            .insert(id, handle);
+        let mut pool = self.thread_pool.lock().expect("Failed to lock thread pool");
+        let mut free_ids = self.free_ids.lock().expect("Failed to lock free ids");
+        let id = free_ids.pop_first().unwrap_or_else(|| next_unused(&pool));
+        pool.insert(id, handle);
         id
     }
     /// Free the generator
     pub fn free_generator(&self, id: GeneratorId) {
-        self.thread_pool
-            .lock()
-            .expect("Failed to lock thread pool")
-            .remove(&id);
+        let mut pool = self.thread_pool.lock().expect("Failed to lock thread pool");
+        let mut free_ids = self.free_ids.lock().expect("Failed to lock free ids");
+        if pool.remove(&id).is_some() {
+            free_ids.insert(id);
+            // Ids at or above the new end of the pool are not gaps anymore
+            free_ids.split_off(&next_unused(&pool));
+        }
     }
 }
 
+/// The id after the largest one in the pool
+fn next_unused(pool: &BTreeMap<GeneratorId, NodeHandle>) -> GeneratorId {
+    pool.keys().next_back().map_or(0, |id| id + 1)
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
 
     use super::*;
+    #[cfg(feature = "jvm")]
     use crate::generator::elle_rw::ElleRwGenerator;
+    use crate::{generator::cyclic::CyclicOpGenerator, history::HistoryType, op::Op};
 
+    #[cfg(feature = "jvm")]
     #[test]
     fn test_alloc_and_free_generator() {
         let rt = madsim::runtime::Runtime::new();
@@ -75,4 +161,109 @@ mod tests {
         gen.free_generator(1);
         assert_eq!(gen.alloc_new_generator(rt.create_node().build()), 1);
     }
+
+    /// The id allocation before the free list, which scans the pool
+    fn scan_next_id(pool: &BTreeSet<GeneratorId>) -> GeneratorId {
+        for (index, id) in pool.iter().enumerate() {
+            if index as u64 != *id {
+                return index as u64;
+            }
+        }
+        pool.len() as u64
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_alloc_and_free_many_ids() {
+        let rt = madsim::runtime::Runtime::new();
+        let node = rt.create_node().build();
+        rt.block_on(async move {
+            let gen = Arc::new(CyclicOpGenerator::new(vec![Op::Read(1, None)]));
+            let global = Global::new(gen);
+            let mut expected = BTreeSet::new();
+            // Deterministic pseudo-random interleaving of allocs and frees
+            let mut state = 1_u64;
+            for _ in 0..10_000 {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1);
+                if expected.is_empty() || !(state >> 33).is_multiple_of(3) {
+                    let next = scan_next_id(&expected);
+                    assert_eq!(global.get_next_id(), next);
+                    assert_eq!(global.alloc_new_generator(node.clone()), next);
+                    expected.insert(next);
+                } else {
+                    let id = *expected
+                        .iter()
+                        .nth((state >> 33) as usize % expected.len())
+                        .unwrap();
+                    global.free_generator(id);
+                    expected.remove(&id);
+                }
+            }
+            assert!(expected.len() > 1000);
+            assert_eq!(global.get_next_id(), scan_next_id(&expected));
+            for id in expected.clone() {
+                global.free_generator(id);
+            }
+            assert_eq!(global.get_next_id(), 0);
+        });
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_flush_history() {
+        let run = |batched: bool| {
+            madsim::runtime::Runtime::new().block_on(async move {
+                let gen = Arc::new(CyclicOpGenerator::new(vec![Op::Read(1, None)]));
+                let global = Arc::new(Global::new(gen));
+                let mut batches = vec![SerializableHistoryList::default(); 2];
+                for step in 0..20_u64 {
+                    let process = step % 2;
+                    let value = Op::Write(1, step);
+                    let mut record = |f: &dyn Fn(&mut SerializableHistoryList)| {
+                        if batched {
+                            f(&mut batches[process as usize]);
+                        } else {
+                            f(&mut global.history.lock().unwrap());
+                        }
+                    };
+                    record(&|his| his.push_invoke(&global, process, value.clone()));
+                    time::sleep(Duration::from_millis(1)).await;
+                    record(&|his| {
+                        his.push_result(&global, process, HistoryType::Ok, value.clone(), None)
+                    });
+                    time::sleep(Duration::from_millis(1)).await;
+                    if batched && step % 5 == 4 {
+                        global.flush_history(&mut batches[process as usize]);
+                    }
+                }
+                for batch in &mut batches {
+                    global.flush_history(batch);
+                }
+                let mut his = global.history.lock().unwrap().clone();
+                his.sort_by_time();
+                serde_json::to_value(his).unwrap()
+            })
+        };
+        assert_eq!(run(true), run(false));
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_with_start_time() {
+        madsim::runtime::Runtime::new().block_on(async {
+            let gen = Arc::new(CyclicOpGenerator::new(vec![Op::Read(1, None)]));
+            let start = time::Instant::now();
+            time::sleep(Duration::from_secs(10)).await;
+            let late = Arc::new(Global::new(gen.clone()));
+            let early = Arc::new(Global::new(gen).with_start_time(start));
+
+            let mut his = SerializableHistoryList::default();
+            his.push_invoke(&late, 0, Op::Read(1, None));
+            his.push_invoke(&early, 0, Op::Read(1, None));
+            assert!(his[0].time < Duration::from_secs(1).as_nanos() as u64);
+            assert!(his[1].time >= Duration::from_secs(10).as_nanos() as u64);
+        });
+    }
 }
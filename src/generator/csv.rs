@@ -0,0 +1,163 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    vec,
+};
+
+use anyhow::{anyhow, bail, Context};
+
+use super::RawGenerator;
+use crate::op::Op;
+
+/// A raw generator replaying ops read from a comma or tab separated file,
+/// e.g. a workload captured elsewhere. Unlike the other raw generators it is
+/// finite, [`RawGenerator::get_op`] returns an error once all rows are used.
+///
+/// Each row is `op,key,value`:
+///
+/// - `op` is `r` (read) or `w` (write)
+/// - `key` is a `u64`
+/// - `value` is a `u64`, required for writes and optional for reads
+///
+/// Fields may be separated by `,` or `\t` and are trimmed. Blank lines, lines
+/// starting with `#` and an `op,key,value` header row are skipped.
+pub struct CsvOpGenerator {
+    ops: vec::IntoIter<Op>,
+}
+
+impl CsvOpGenerator {
+    /// Read all ops from the file at `path`.
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).with_context(|| format!("open {}", path.display()))?;
+        Self::from_reader(file).with_context(|| format!("parse {}", path.display()))
+    }
+
+    /// Read all ops from `r`. Malformed rows are reported with their line
+    /// number.
+    pub fn from_reader(r: impl Read) -> anyhow::Result<Self> {
+        let mut ops = vec![];
+        for (i, line) in BufReader::new(r).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || is_header(line) {
+                continue;
+            }
+            ops.push(parse_row(line).with_context(|| format!("line {}: `{line}`", i + 1))?);
+        }
+        Ok(Self {
+            ops: ops.into_iter(),
+        })
+    }
+}
+
+fn fields(line: &str) -> Vec<&str> {
+    line.split([',', '\t']).map(str::trim).collect()
+}
+
+fn is_header(line: &str) -> bool {
+    fields(line) == ["op", "key", "value"]
+}
+
+/// Parse a single non-empty row into an [`Op`]
+fn parse_row(line: &str) -> anyhow::Result<Op> {
+    let fields = fields(line);
+    let (op, key, value) = match fields[..] {
+        [op, key] => (op, key, ""),
+        [op, key, value] => (op, key, value),
+        _ => bail!("expected 2 or 3 fields, got {}", fields.len()),
+    };
+    let key = key
+        .parse::<u64>()
+        .map_err(|e| anyhow!("invalid key `{key}`: {e}"))?;
+    let value = match value {
+        "" => None,
+        v => Some(
+            v.parse::<u64>()
+                .map_err(|e| anyhow!("invalid value `{v}`: {e}"))?,
+        ),
+    };
+    match op {
+        "r" => Ok(Op::Read(key, value)),
+        "w" => Ok(Op::Write(
+            key,
+            value.ok_or_else(|| anyhow!("write without value"))?,
+        )),
+        _ => bail!("unknown op type `{op}`"),
+    }
+}
+
+impl RawGenerator for CsvOpGenerator {
+    fn get_op(&mut self) -> anyhow::Result<Op> {
+        self.ops.next().ok_or_else(|| anyhow!("no more ops in csv"))
+    }
+}
+
+impl Iterator for CsvOpGenerator {
+    type Item = anyhow::Result<Op>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.ops.next().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn csv_gen_should_replay_rows() -> anyhow::Result<()> {
+        let csv = "op,key,value\n\
+                   # captured workload\n\
+                   w,1,10\n\
+                   r,1\n\
+                   \n\
+                   r\t2\t5\n\
+                   r,3,\n";
+        let mut gen = CsvOpGenerator::from_reader(csv.as_bytes())?;
+        let ops = gen.by_ref().collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(
+            ops,
+            vec![
+                Op::Write(1, 10),
+                Op::Read(1, None),
+                Op::Read(2, Some(5)),
+                Op::Read(3, None),
+            ]
+        );
+        assert!(gen.get_op().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn csv_gen_should_read_from_path() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(format!("jepsen-rs-csv-{}.tsv", std::process::id()));
+        std::fs::write(&path, "op\tkey\tvalue\nw\t1\t2\nr\t1\t\n")?;
+        let res = CsvOpGenerator::from_path(&path)
+            .and_then(|gen| gen.collect::<anyhow::Result<Vec<_>>>());
+        std::fs::remove_file(&path)?;
+        assert_eq!(res?, vec![Op::Write(1, 2), Op::Read(1, None)]);
+
+        let err = CsvOpGenerator::from_path(&path).err().unwrap();
+        assert!(format!("{err:#}").contains(&path.display().to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn csv_gen_should_reject_malformed_rows() {
+        for (csv, msg) in [
+            ("w,1\n", "write without value"),
+            ("x,1,2\n", "unknown op type"),
+            ("r,one\n", "invalid key"),
+            ("r,1,2,3\n", "expected 2 or 3 fields"),
+        ] {
+            let err = CsvOpGenerator::from_reader(format!("r,0\n{csv}").as_bytes())
+                .err()
+                .unwrap();
+            let err = format!("{err:#}");
+            assert!(err.starts_with("line 2:"), "{err}");
+            assert!(err.contains(msg), "{err}");
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use super::RawGenerator;
+use crate::op::Op;
+
+/// A raw generator that cycles through a fixed set of pre-built `Op`s. It
+/// needs no JVM and does no work beyond cloning the next op, which makes it
+/// the recommended generator for benchmarking the generator machinery.
+pub struct CyclicOpGenerator {
+    /// The ops to cycle through, never empty
+    ops: Vec<Op>,
+    /// The position of the next op
+    pos: usize,
+}
+
+impl CyclicOpGenerator {
+    /// Create a generator cycling through `ops`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `ops` is empty.
+    pub fn new(ops: Vec<Op>) -> Self {
+        assert!(!ops.is_empty(), "ops must not be empty");
+        Self { ops, pos: 0 }
+    }
+}
+
+impl RawGenerator for CyclicOpGenerator {
+    fn get_op(&mut self) -> anyhow::Result<Op> {
+        let op = self.ops[self.pos].clone();
+        self.pos = (self.pos + 1) % self.ops.len();
+        Ok(op)
+    }
+}
+
+impl Iterator for CyclicOpGenerator {
+    type Item = anyhow::Result<Op>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_op())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cyclic_gen_should_work() -> anyhow::Result<()> {
+        let ops = vec![
+            Op::Write(1, 1),
+            Op::Read(1, None),
+            Op::Txn(vec![Op::Write(2, 1), Op::Read(1, None)]),
+        ];
+        let mut gen = CyclicOpGenerator::new(ops.clone());
+        for i in 0..1_000_000 {
+            assert_eq!(gen.get_op()?, ops[i % ops.len()]);
+        }
+        Ok(())
+    }
+}
@@ -4,7 +4,6 @@ use j4rs::{Instance, InvocationArg};
 
 use super::{RawGenerator, GENERATOR_CACHE_SIZE};
 use crate::{
-    cljinvoke, nsinvoke,
     op::{Op, Ops},
     utils::{pre_serialize, ToDe},
     with_jvm, CljNs, CLOJURE,
@@ -13,12 +12,16 @@ use crate::{
 pub struct ElleRwGenerator {
     /// The namespace of the generator, default is `elle.rw-register`
     ns: CljNs,
-    /// The clojure generator Instance.
+    /// The clojure generator Instance. It is kept as a raw `Instance` so that
+    /// the generator stays `Send` and `Sync` behind `Global`'s `Arc`.
     gen: Mutex<Option<Instance>>,
     /// The cached `Op`s of the generator. Because the clojure generator will
     /// generates infinite sequence, we can take some of them to cache. When the
     /// `Op`s run out, fetch new `Op`s from the clojure generator.
     cache: Ops,
+    /// How many `Op`s are fetched from the clojure generator per refill,
+    /// default is [`GENERATOR_CACHE_SIZE`].
+    cache_size: usize,
 }
 
 impl ElleRwGenerator {
@@ -29,9 +32,24 @@ impl ElleRwGenerator {
                 ns,
                 gen: Mutex::new(None),
                 cache: Ops(Vec::with_capacity(GENERATOR_CACHE_SIZE)),
+                cache_size: GENERATOR_CACHE_SIZE,
             })
         })
     }
+
+    /// Set how many `Op`s are fetched from the clojure generator per refill.
+    /// A small size exercises the refill logic, a large one reduces the JVM
+    /// round trips.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn with_cache_size(mut self, n: usize) -> Self {
+        assert!(n >= 1, "cache size must be at least 1");
+        self.cache_size = n;
+        self.cache = Ops(Vec::with_capacity(n));
+        self
+    }
 }
 
 impl RawGenerator for ElleRwGenerator {
@@ -43,7 +61,7 @@ impl RawGenerator for ElleRwGenerator {
         }
         let mut gen = self.gen.lock().expect("Failed to lock generator");
         if gen.is_none() {
-            gen.replace(nsinvoke!(self.ns, "gen")?);
+            gen.replace(nsinvoke!(self.ns, "gen")?.into_instance());
         }
         let cljgen = gen
             .take()
@@ -52,7 +70,7 @@ impl RawGenerator for ElleRwGenerator {
         // avoid consuming the ownership of `two_seqs`
         let two_seqs = [InvocationArg::from(cljinvoke!(
             "split-at",
-            GENERATOR_CACHE_SIZE as i32,
+            self.cache_size as i32,
             cljgen
         )?)];
 
@@ -62,7 +80,7 @@ impl RawGenerator for ElleRwGenerator {
 
         let second_seq = CLOJURE.var("second")?.invoke(&two_seqs)?;
         // update the elle gen
-        gen.replace(second_seq);
+        gen.replace(second_seq.into_instance());
         Ok(self
             .cache
             .pop()
@@ -92,4 +110,16 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn elle_gen_with_tiny_cache_should_work() -> Result<(), Box<dyn std::error::Error>> {
+        init_jvm();
+        let mut gen = ElleRwGenerator::new()?.with_cache_size(1);
+        let ops = (0..50)
+            .map(|_| gen.get_op())
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        assert_eq!(ops.len(), 50);
+        assert!(ops.iter().all(Op::is_txn));
+        Ok(())
+    }
 }
@@ -0,0 +1,166 @@
+use super::RawGenerator;
+use crate::op::Op;
+
+/// The max number of ops in a txn generated by [`MixedOpGenerator`]
+const MAX_TXN_LEN: u64 = 4;
+
+/// A small deterministic PRNG (SplitMix64), good enough for shaping
+/// workloads without pulling in a rand dependency.
+pub(super) struct SplitMix64(u64);
+
+impl SplitMix64 {
+    pub(super) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(super) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number in `0..n`
+    pub(super) fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+}
+
+/// The relative weights of each op type generated by [`MixedOpGenerator`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpWeights {
+    pub read: u32,
+    pub write: u32,
+    pub txn: u32,
+}
+
+impl OpWeights {
+    fn total(&self) -> u64 {
+        u64::from(self.read) + u64::from(self.write) + u64::from(self.txn)
+    }
+}
+
+/// A raw generator producing a deterministic mix of reads, writes and txns
+/// over keys `0..key_count`, without the JVM. Every write gets a unique
+/// value, as Elle expects.
+pub struct MixedOpGenerator {
+    rng: SplitMix64,
+    key_count: u64,
+    weights: OpWeights,
+    /// The value of the next write
+    next_value: u64,
+}
+
+impl MixedOpGenerator {
+    /// Create a generator with the given seed, key count and op weights.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key_count` is 0 or the weights sum to 0.
+    pub fn new(seed: u64, key_count: u64, weights: OpWeights) -> Self {
+        assert!(key_count >= 1, "key count must be at least 1");
+        assert!(weights.total() > 0, "weights must sum to a positive value");
+        Self {
+            rng: SplitMix64::new(seed),
+            key_count,
+            weights,
+            next_value: 1,
+        }
+    }
+
+    fn read(&mut self) -> Op {
+        Op::Read(self.rng.below(self.key_count), None)
+    }
+
+    fn write(&mut self) -> Op {
+        let value = self.next_value;
+        self.next_value += 1;
+        Op::Write(self.rng.below(self.key_count), value)
+    }
+}
+
+impl RawGenerator for MixedOpGenerator {
+    fn get_op(&mut self) -> anyhow::Result<Op> {
+        let read = u64::from(self.weights.read);
+        let write = u64::from(self.weights.write);
+        let pick = self.rng.below(self.weights.total());
+        let op = if pick < read {
+            self.read()
+        } else if pick < read + write {
+            self.write()
+        } else {
+            let len = self.rng.below(MAX_TXN_LEN) + 1;
+            Op::Txn(
+                (0..len)
+                    .map(|_| {
+                        if self.rng.below(2) == 0 {
+                            self.read()
+                        } else {
+                            self.write()
+                        }
+                    })
+                    .collect(),
+            )
+        };
+        Ok(op)
+    }
+}
+
+impl Iterator for MixedOpGenerator {
+    type Item = anyhow::Result<Op>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.get_op())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WEIGHTS: OpWeights = OpWeights {
+        read: 70,
+        write: 20,
+        txn: 10,
+    };
+
+    #[test]
+    fn mixed_gen_should_follow_weights() -> anyhow::Result<()> {
+        let total = 100_000;
+        let ops = MixedOpGenerator::new(42, 10, WEIGHTS)
+            .take(total)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let ratio =
+            |f: fn(&Op) -> bool| ops.iter().filter(|op| f(op)).count() as f64 / total as f64;
+        assert!((ratio(Op::is_read) - 0.7).abs() < 0.01);
+        assert!((ratio(Op::is_write) - 0.2).abs() < 0.01);
+        assert!((ratio(Op::is_txn) - 0.1).abs() < 0.01);
+        assert!(ops.iter().flat_map(Op::keys).all(|key| key < 10));
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_gen_should_be_deterministic() -> anyhow::Result<()> {
+        let a = MixedOpGenerator::new(7, 5, WEIGHTS).take(100);
+        let b = MixedOpGenerator::new(7, 5, WEIGHTS).take(100);
+        for (a, b) in a.zip(b) {
+            assert_eq!(a?, b?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "weights must sum to a positive value")]
+    fn mixed_gen_should_reject_zero_weights() {
+        MixedOpGenerator::new(
+            0,
+            1,
+            OpWeights {
+                read: 0,
+                write: 0,
+                txn: 0,
+            },
+        );
+    }
+}
@@ -1,9 +1,22 @@
 pub mod context;
+mod csv;
+mod cyclic;
+#[cfg(feature = "jvm")]
 mod elle_rw;
+mod mixed;
+mod phased;
+mod tee;
 use std::{collections::HashMap, sync::Arc};
 
 pub use context::Global;
-use madsim::runtime::NodeHandle;
+pub use csv::CsvOpGenerator;
+pub use cyclic::CyclicOpGenerator;
+#[cfg(feature = "jvm")]
+pub use elle_rw::ElleRwGenerator;
+use mixed::SplitMix64;
+pub use mixed::{MixedOpGenerator, OpWeights};
+pub use phased::{Phase, PhasedSeq};
+pub use tee::{tee, Tee};
 
 use crate::op::Op;
 
@@ -11,8 +24,18 @@ use crate::op::Op;
 pub type GeneratorId = u64;
 
 /// Cache size for the generator.
+#[cfg(feature = "jvm")]
 pub const GENERATOR_CACHE_SIZE: usize = 200;
 
+/// What to do with the errors of a generator, see [`Generator::ok_ops`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Drop the failed elements and keep generating
+    Skip,
+    /// End the generator at the first error
+    Stop,
+}
+
 /// This trait is for the raw generator (clojure generator), which will only
 /// generate ops infinitely.
 pub trait RawGenerator {
@@ -20,6 +43,13 @@ pub trait RawGenerator {
 }
 
 /// The generator. It's a wrapper for the clojure seq and global context.
+///
+/// There is no error type parameter: generation errors are part of the
+/// element type `U`, `anyhow::Result<Op>` by default, and are adapted with
+/// the sequence combinators (see [`Generator::ok_ops`]). The error type of
+/// the recorded history is a parameter of
+/// [`crate::history::SerializableHistoryList`] instead, as [`Global`] always
+/// records with the default one.
 pub struct Generator<T: Iterator<Item = U>, U = anyhow::Result<Op>> {
     /// generator id
     pub id: GeneratorId,
@@ -34,4 +64,468 @@ impl<T: Iterator<Item = anyhow::Result<Op>>> Generator<T> {
         let id = global.get_next_id();
         Self { id, global, seq }
     }
+
+    /// Rewrite the value of every write (including those in txns) to the
+    /// last value written to the same key plus one, starting from 1, so that
+    /// the values of each key are monotonic. Reads are untouched.
+    pub fn monotonic_per_key(self) -> Generator<impl Iterator<Item = anyhow::Result<Op>>> {
+        let mut last = HashMap::new();
+        self.map_seq(|seq| {
+            seq.map(move |op| {
+                op.map(|mut op| {
+                    make_monotonic(&mut op, &mut last);
+                    op
+                })
+            })
+        })
+    }
+
+    /// Unwrap the generated `Op`s, handling the errors of a fallible raw
+    /// generator according to `policy` instead of panicking on them. Each
+    /// skipped error is logged as a warning, so a generator failing every
+    /// time doesn't pass for an empty one.
+    pub fn ok_ops(self, policy: ErrorPolicy) -> Generator<impl Iterator<Item = Op>, Op> {
+        self.map_seq(move |seq| {
+            seq.map_while(move |op| match (op, policy) {
+                (Ok(op), _) => Some(Some(op)),
+                (Err(e), ErrorPolicy::Skip) => {
+                    log::warn!("skipping generator error: {e:#}");
+                    Some(None)
+                }
+                (Err(_), ErrorPolicy::Stop) => None,
+            })
+            .flatten()
+        })
+    }
+
+    /// Merge runs of consecutive single-write txns, like Elle's `[[:w k v]]`,
+    /// into one txn of up to `max` writes, e.g. to exercise batching. Any
+    /// other element (reads, multi-op txns, errors) breaks the run and is
+    /// passed through unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is 0.
+    pub fn coalesce_singletons(
+        self,
+        max: usize,
+    ) -> Generator<impl Iterator<Item = anyhow::Result<Op>>> {
+        assert!(max >= 1, "max must be at least 1");
+        let is_single_write = |op: &anyhow::Result<Op>| matches!(op, Ok(Op::Txn(ops)) if matches!(ops[..], [Op::Write(_, _)]));
+        self.map_seq(move |seq| {
+            let mut seq = seq.peekable();
+            std::iter::from_fn(move || {
+                let first = seq.next()?;
+                if !is_single_write(&first) {
+                    return Some(first);
+                }
+                let mut writes = first.ok()?.flatten_txn();
+                while writes.len() < max {
+                    match seq.next_if(is_single_write) {
+                        Some(Ok(op)) => writes.extend(op.flatten_txn().0),
+                        _ => break,
+                    }
+                }
+                Some(Ok(writes.into_single_txn()))
+            })
+        })
+    }
+
+    /// Insert `op` after every `every_ops` elements of the sequence, e.g. a
+    /// no-op read to keep a connection warm or to mark time in bursty
+    /// histories. No heartbeat follows the last element.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every_ops` is 0.
+    pub fn heartbeat(
+        self,
+        every_ops: usize,
+        op: Op,
+    ) -> Generator<impl Iterator<Item = anyhow::Result<Op>>> {
+        assert!(every_ops >= 1, "every_ops must be at least 1");
+        self.map_seq(move |seq| {
+            let mut seq = seq.peekable();
+            let mut count = 0;
+            std::iter::from_fn(move || {
+                if count == every_ops && seq.peek().is_some() {
+                    count = 0;
+                    return Some(Ok(op.clone()));
+                }
+                count += 1;
+                seq.next()
+            })
+        })
+    }
+
+    /// Split into two generators yielding the same sequence, e.g. to run the
+    /// same workload against two clusters. Both halves keep the id of `self`.
+    ///
+    /// The elements pulled by one half are buffered until the other half
+    /// consumes them, so memory grows with the distance between the two; if
+    /// one half is never consumed, the whole sequence ends up buffered. Errors
+    /// are buffered too, rebuilt from their message for both halves, so both
+    /// see the same error at the same position.
+    pub fn tee(
+        self,
+    ) -> (
+        Generator<impl Iterator<Item = anyhow::Result<Op>>>,
+        Generator<impl Iterator<Item = anyhow::Result<Op>>>,
+    ) {
+        let (id, global) = (self.id, self.global);
+        let (left, right) = tee::tee_seq(self.seq, tee::split_result);
+        (
+            Generator {
+                id,
+                global: global.clone(),
+                seq: left,
+            },
+            Generator {
+                id,
+                global,
+                seq: right,
+            },
+        )
+    }
+}
+
+impl Generator<PhasedSeq> {
+    /// Create a generator switching the op mix as generation goes on, e.g. a
+    /// read-heavy phase followed by a write-heavy one. Each phase produces
+    /// ops from its factory for the given number of ops before advancing to
+    /// the next, and the phases cycle forever. Phases lasting 0 ops are
+    /// skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no phase lasts at least one op.
+    pub fn phased(global: Arc<Global>, phases: Vec<Phase>) -> Self {
+        Self::new(global, PhasedSeq::new(phases))
+    }
+}
+
+/// Rewrite the write values in `op` according to the last value of each key.
+fn make_monotonic(op: &mut Op, last: &mut HashMap<u64, u64>) {
+    match op {
+        Op::Read(_, _) => {}
+        Op::Write(key, value) => {
+            let next = last.entry(*key).or_insert(0);
+            *next += 1;
+            *value = *next;
+        }
+        Op::Txn(ops) => ops.iter_mut().for_each(|op| make_monotonic(op, last)),
+    }
+}
+
+impl<T: Iterator<Item = U>, U> Generator<T, U> {
+    /// Transform the inner sequence, keeping the id and global context.
+    fn map_seq<T2, U2, F>(self, f: F) -> Generator<T2, U2>
+    where
+        T2: Iterator<Item = U2>,
+        F: FnOnce(T) -> T2,
+    {
+        Generator {
+            id: self.id,
+            global: self.global,
+            seq: f(self.seq),
+        }
+    }
+
+    /// Merge with another generator in the order of `key_fn`, assuming both
+    /// sequences are already sorted by it. On equal keys, elements of `self`
+    /// come first. The merged generator keeps the id of `self`.
+    pub fn merge_sorted_by<T2, K, F>(
+        self,
+        other: Generator<T2, U>,
+        mut key_fn: F,
+    ) -> Generator<impl Iterator<Item = U>, U>
+    where
+        T2: Iterator<Item = U>,
+        K: Ord,
+        F: FnMut(&U) -> K,
+    {
+        self.map_seq(|seq| {
+            let mut left = seq.peekable();
+            let mut right = other.seq.peekable();
+            std::iter::from_fn(move || match (left.peek(), right.peek()) {
+                (Some(l), Some(r)) if key_fn(r) < key_fn(l) => right.next(),
+                (Some(_), _) => left.next(),
+                (None, _) => right.next(),
+            })
+        })
+    }
+
+    /// Locally reorder the sequence: buffer `window` elements and emit them
+    /// in an order permuted by a PRNG seeded with `seed`, then repeat. The
+    /// last window may be shorter. The same seed always gives the same
+    /// order, and no element moves out of its window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `window` is 0.
+    pub fn shuffle_windows(
+        self,
+        window: usize,
+        seed: u64,
+    ) -> Generator<impl Iterator<Item = U>, U> {
+        assert!(window >= 1, "window must be at least 1");
+        let mut rng = SplitMix64::new(seed);
+        let mut buf = Vec::with_capacity(window);
+        self.map_seq(move |mut seq| {
+            std::iter::from_fn(move || {
+                if buf.is_empty() {
+                    buf.extend(seq.by_ref().take(window));
+                    // Fisher-Yates, reversed so that `pop` emits in order
+                    for i in (1..buf.len()).rev() {
+                        buf.swap(i, rng.below(i as u64 + 1) as usize);
+                    }
+                    buf.reverse();
+                }
+                buf.pop()
+            })
+        })
+    }
+
+    /// Tag each element with its index in the sequence, like
+    /// [`Iterator::enumerate`] but keeping the id and global context. Note
+    /// that the element type changes from `U` to `(usize, U)`.
+    pub fn enumerate(self) -> Generator<impl Iterator<Item = (usize, U)>, (usize, U)> {
+        self.map_seq(Iterator::enumerate)
+    }
+}
+
+impl<T: Iterator<Item = U>, U> Iterator for Generator<T, U> {
+    type Item = U;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.seq.next()
+    }
+
+    /// The remaining length of the inner sequence. It is exact for finite
+    /// sources like a `Vec`, and has no upper bound for unknown or infinite
+    /// ones.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.seq.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A raw generator that never generates anything, for tests which only
+    /// need a global context.
+    struct EmptyGenerator;
+
+    impl RawGenerator for EmptyGenerator {
+        fn get_op(&mut self) -> anyhow::Result<Op> {
+            anyhow::bail!("empty generator")
+        }
+    }
+
+    /// Run `f` with a fresh global context inside the madsim runtime.
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn with_global<R>(f: impl FnOnce(Arc<Global>) -> R) -> R {
+        madsim::runtime::Runtime::new()
+            .block_on(async move { f(Arc::new(Global::new(Arc::new(EmptyGenerator)))) })
+    }
+
+    fn writes(keys: &[u64]) -> impl Iterator<Item = anyhow::Result<Op>> + '_ {
+        keys.iter().map(|&k| Ok(Op::Write(k, 1)))
+    }
+
+    fn keys(gen: impl Iterator<Item = anyhow::Result<Op>>) -> Vec<u64> {
+        gen.map(|op| op.unwrap().key().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_merge_sorted_by() {
+        with_global(|global| {
+            let left = Generator::new(global.clone(), writes(&[1, 4, 5, 9]));
+            let right = Generator::new(global, writes(&[2, 3, 5, 10, 11]));
+            let merged = left.merge_sorted_by(right, |op| op.as_ref().ok().and_then(Op::key));
+            assert_eq!(keys(merged), vec![1, 2, 3, 4, 5, 5, 9, 10, 11]);
+        });
+    }
+
+    #[test]
+    fn test_size_hint() {
+        with_global(|global| {
+            let mut gen = Generator::new(global.clone(), writes(&[1, 2, 3]));
+            assert_eq!(gen.size_hint(), (3, Some(3)));
+            gen.next();
+            assert_eq!(gen.size_hint(), (2, Some(2)));
+            gen.by_ref().for_each(drop);
+            assert_eq!(gen.size_hint(), (0, Some(0)));
+
+            let gen = Generator::new(global, std::iter::repeat_with(|| Ok(Op::Read(1, None))));
+            assert_eq!(gen.size_hint(), (usize::MAX, None));
+        });
+    }
+
+    #[test]
+    fn test_enumerate() {
+        with_global(|global| {
+            let gen = Generator::new(global, writes(&[1, 2, 3, 4, 5, 6]))
+                .map_seq(|seq| seq.filter(|op| op.as_ref().unwrap().key().unwrap() % 2 == 0))
+                .enumerate();
+            let res = gen
+                .map(|(i, op)| (i, op.unwrap().key().unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(res, vec![(0, 2), (1, 4), (2, 6)]);
+        });
+    }
+
+    #[test]
+    fn test_shuffle_windows() {
+        with_global(|global| {
+            let input = (0..23).collect::<Vec<u64>>();
+            let shuffle = |seed| {
+                let gen = Generator::new(global.clone(), writes(&input)).shuffle_windows(5, seed);
+                keys(gen)
+            };
+            let res = shuffle(42);
+            assert_eq!(res, shuffle(42));
+            assert_ne!(res, input);
+            for (shuffled, chunk) in res.chunks(5).zip(input.chunks(5)) {
+                let mut sorted = shuffled.to_vec();
+                sorted.sort_unstable();
+                assert_eq!(sorted, chunk);
+            }
+        });
+    }
+
+    #[test]
+    fn test_phased() {
+        with_global(|global| {
+            let gen = Generator::phased(
+                global,
+                vec![
+                    (3, Box::new(|| Op::Read(1, None))),
+                    (0, Box::new(|| unreachable!())),
+                    (2, Box::new(|| Op::Write(1, 1))),
+                ],
+            );
+            let ops = gen.take(8).map(Result::unwrap).collect::<Vec<_>>();
+            let reads = ops.iter().map(Op::is_read).collect::<Vec<_>>();
+            assert_eq!(
+                reads,
+                vec![true, true, true, false, false, true, true, true]
+            );
+        });
+    }
+
+    #[test]
+    fn test_coalesce_singletons() {
+        with_global(|global| {
+            let single = |key| Op::Txn(vec![Op::Write(key, 1)]);
+            let ops = vec![
+                single(1),
+                single(2),
+                single(3),
+                single(4),
+                Op::Txn(vec![Op::Read(5, None)]),
+                single(6),
+                Op::Txn(vec![Op::Write(7, 1), Op::Write(8, 1)]),
+                single(9),
+                single(10),
+            ];
+            let gen = Generator::new(global, ops.into_iter().map(Ok)).coalesce_singletons(3);
+            let res = gen.map(|op| op.unwrap().keys()).collect::<Vec<_>>();
+            assert_eq!(
+                res,
+                vec![
+                    vec![1, 2, 3],
+                    vec![4],
+                    vec![5],
+                    vec![6],
+                    vec![7, 8],
+                    vec![9, 10],
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_heartbeat() {
+        with_global(|global| {
+            let gen =
+                Generator::new(global, writes(&[1, 2, 3, 4, 5, 6])).heartbeat(2, Op::Read(0, None));
+            assert_eq!(keys(gen), vec![1, 2, 0, 3, 4, 0, 5, 6]);
+        });
+    }
+
+    #[test]
+    fn test_tee() {
+        with_global(|global| {
+            let seq = writes(&[1, 2, 3, 4, 5])
+                .chain(std::iter::once(Err(anyhow::anyhow!("boom"))))
+                .chain(writes(&[6]));
+            let (mut left, right) = Generator::new(global, seq).tee();
+            let prefix = left
+                .by_ref()
+                .take(2)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>();
+            let rhs = right
+                .map(|op| op.map_err(|e| e.to_string()))
+                .collect::<Vec<_>>();
+            let lhs = prefix
+                .into_iter()
+                .map(Ok)
+                .chain(left.map(|op| op.map_err(|e| e.to_string())))
+                .collect::<Vec<_>>();
+            assert_eq!(lhs, rhs);
+            assert_eq!(rhs.len(), 7);
+            assert_eq!(lhs[5], Err("boom".to_string()));
+            assert_eq!(rhs[5], Err("boom".to_string()));
+        });
+    }
+
+    #[test]
+    fn test_ok_ops() {
+        let seq = || {
+            writes(&[1, 2])
+                .chain(std::iter::once(Err(anyhow::anyhow!("boom"))))
+                .chain(writes(&[3]))
+                .collect::<Vec<_>>()
+        };
+        with_global(|global| {
+            let gen = Generator::new(global.clone(), seq().into_iter()).ok_ops(ErrorPolicy::Skip);
+            let keys = gen.map(|op| op.key().unwrap()).collect::<Vec<_>>();
+            assert_eq!(keys, vec![1, 2, 3]);
+
+            let infinite = seq()
+                .into_iter()
+                .chain(std::iter::repeat_with(|| Ok(Op::Write(4, 1))));
+            let gen = Generator::new(global, infinite).ok_ops(ErrorPolicy::Stop);
+            let keys = gen.map(|op| op.key().unwrap()).collect::<Vec<_>>();
+            assert_eq!(keys, vec![1, 2]);
+        });
+    }
+
+    #[test]
+    fn test_monotonic_per_key() {
+        with_global(|global| {
+            let ops = vec![
+                Op::Write(1, 7),
+                Op::Write(2, 7),
+                Op::Read(1, Some(7)),
+                Op::Txn(vec![Op::Write(2, 0), Op::Write(1, 0), Op::Read(2, None)]),
+                Op::Write(1, 9),
+            ];
+            let gen = Generator::new(global, ops.into_iter().map(Ok)).monotonic_per_key();
+            let res = gen.map(Result::unwrap).collect::<Vec<_>>();
+            assert_eq!(
+                res,
+                vec![
+                    Op::Write(1, 1),
+                    Op::Write(2, 1),
+                    Op::Read(1, Some(7)),
+                    Op::Txn(vec![Op::Write(2, 2), Op::Write(1, 2), Op::Read(2, None)]),
+                    Op::Write(1, 3),
+                ]
+            );
+        });
+    }
 }
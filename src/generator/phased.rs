@@ -0,0 +1,49 @@
+use crate::op::Op;
+
+/// A phase of [`super::Generator::phased`]: the number of ops it lasts and
+/// the factory producing them
+pub type Phase = (usize, Box<dyn Fn() -> Op>);
+
+/// The sequence of [`super::Generator::phased`], which cycles through its
+/// phases forever.
+pub struct PhasedSeq {
+    phases: Vec<Phase>,
+    /// The current phase
+    phase: usize,
+    /// The number of ops already emitted in the current phase
+    emitted: usize,
+}
+
+impl PhasedSeq {
+    /// # Panics
+    ///
+    /// Panics if no phase lasts at least one op.
+    pub(super) fn new(phases: Vec<Phase>) -> Self {
+        assert!(
+            phases.iter().any(|(len, _)| *len > 0),
+            "at least one phase must last at least one op"
+        );
+        Self {
+            phases,
+            phase: 0,
+            emitted: 0,
+        }
+    }
+}
+
+impl Iterator for PhasedSeq {
+    type Item = anyhow::Result<Op>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.emitted >= self.phases[self.phase].0 {
+            self.phase = (self.phase + 1) % self.phases.len();
+            self.emitted = 0;
+        }
+        self.emitted += 1;
+        Some(Ok((self.phases[self.phase].1)()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
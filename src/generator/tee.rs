@@ -0,0 +1,168 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use super::RawGenerator;
+use crate::op::Op;
+
+/// The state shared by both sides of a [`TeeSeq`]
+struct TeeShared<I: Iterator> {
+    /// The source sequence
+    seq: I,
+    /// The elements yielded by one side but not yet consumed by the other
+    buffers: [VecDeque<I::Item>; 2],
+}
+
+/// Turns a pulled element into one copy for each side of a [`TeeSeq`]
+type Split<T> = fn(T) -> (T, T);
+
+/// One side of a forked sequence. Both sides see the same elements, the
+/// elements pulled by the faster side are buffered until the slower side
+/// consumes them, so the buffer grows with the distance between them.
+pub(super) struct TeeSeq<I: Iterator> {
+    shared: Rc<RefCell<TeeShared<I>>>,
+    side: usize,
+    split: Split<I::Item>,
+}
+
+/// Fork a sequence into two sequences yielding the same elements.
+pub(super) fn tee_seq<I: Iterator>(seq: I, split: Split<I::Item>) -> (TeeSeq<I>, TeeSeq<I>) {
+    let shared = Rc::new(RefCell::new(TeeShared {
+        seq,
+        buffers: [VecDeque::new(), VecDeque::new()],
+    }));
+    (
+        TeeSeq {
+            shared: shared.clone(),
+            side: 0,
+            split,
+        },
+        TeeSeq {
+            shared,
+            side: 1,
+            split,
+        },
+    )
+}
+
+impl<I: Iterator> Iterator for TeeSeq<I> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut shared = self.shared.borrow_mut();
+        if let Some(item) = shared.buffers[self.side].pop_front() {
+            return Some(item);
+        }
+        let (item, copy) = (self.split)(shared.seq.next()?);
+        shared.buffers[1 - self.side].push_back(copy);
+        Some(item)
+    }
+}
+
+/// Split a generated result for [`tee_seq`]. Errors can't be cloned, so both
+/// sides get one rebuilt from the message, keeping them identical.
+pub(super) fn split_result(op: anyhow::Result<Op>) -> (anyhow::Result<Op>, anyhow::Result<Op>) {
+    match op {
+        Ok(op) => (Ok(op.clone()), Ok(op)),
+        Err(e) => {
+            let msg = format!("{e:#}");
+            (Err(anyhow::anyhow!(msg.clone())), Err(anyhow::anyhow!(msg)))
+        }
+    }
+}
+
+/// Adapts a raw generator to an infinite iterator
+struct RawSeq<G>(G);
+
+impl<G: RawGenerator> Iterator for RawSeq<G> {
+    type Item = anyhow::Result<Op>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.0.get_op())
+    }
+}
+
+/// One side of a forked raw generator, see [`tee`]. Errors of the source
+/// generator are buffered as well, so both sides see them at the same
+/// position.
+pub struct Tee<G: RawGenerator>(TeeSeq<RawSeq<G>>);
+
+/// Fork a raw generator into two independent streams of the same `Op`s.
+pub fn tee<G: RawGenerator>(gen: G) -> (Tee<G>, Tee<G>) {
+    let (left, right) = tee_seq(RawSeq(gen), split_result);
+    (Tee(left), Tee(right))
+}
+
+impl<G: RawGenerator> RawGenerator for Tee<G> {
+    fn get_op(&mut self) -> anyhow::Result<Op> {
+        self.0.next().expect("raw generators never end")
+    }
+}
+
+impl<G: RawGenerator> Iterator for Tee<G> {
+    type Item = anyhow::Result<Op>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Generates `Write(n, n)` with an increasing `n`
+    struct CountingGenerator(u64);
+
+    impl RawGenerator for CountingGenerator {
+        fn get_op(&mut self) -> anyhow::Result<Op> {
+            self.0 += 1;
+            Ok(Op::Write(self.0, self.0))
+        }
+    }
+
+    #[test]
+    fn tee_should_yield_same_ops() -> anyhow::Result<()> {
+        let (mut left, mut right) = tee(CountingGenerator(0));
+        let mut lhs = vec![];
+        let mut rhs = vec![];
+        for i in 0..100 {
+            lhs.push(left.get_op()?);
+            if i % 3 == 0 {
+                rhs.push(right.get_op()?);
+            }
+        }
+        while rhs.len() < 150 {
+            rhs.push(right.get_op()?);
+        }
+        while lhs.len() < 150 {
+            lhs.push(left.get_op()?);
+        }
+        assert_eq!(lhs, rhs);
+        assert_eq!(lhs[149], Op::Write(150, 150));
+        Ok(())
+    }
+
+    /// Fails on every third op
+    struct FlakyGenerator(u64);
+
+    impl RawGenerator for FlakyGenerator {
+        fn get_op(&mut self) -> anyhow::Result<Op> {
+            self.0 += 1;
+            if self.0.is_multiple_of(3) {
+                anyhow::bail!("flaky {}", self.0);
+            }
+            Ok(Op::Write(self.0, self.0))
+        }
+    }
+
+    #[test]
+    fn tee_should_yield_same_errors() {
+        let (left, right) = tee(FlakyGenerator(0));
+        let show = |op: anyhow::Result<Op>| op.map_err(|e| e.to_string());
+        let lhs = left.take(7).map(show).collect::<Vec<_>>();
+        let rhs = right.take(7).map(show).collect::<Vec<_>>();
+        assert_eq!(lhs, rhs);
+        assert_eq!(lhs[2], Err("flaky 3".to_string()));
+        assert_eq!(lhs[5], Err("flaky 6".to_string()));
+        assert_eq!(lhs[6], Ok(Op::Write(7, 7)));
+    }
+}
@@ -1,33 +1,234 @@
 use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    io::{BufRead, BufReader, Read, Write},
+    mem::size_of,
     ops::{Deref, DerefMut},
     sync::Arc,
+    time::Duration,
 };
 
+#[cfg(feature = "jvm")]
+use std::{cell::OnceCell, path::Path};
+
 use madsim::time;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{DeserializeOwned, Visitor},
+    Deserialize, Serialize,
+};
+use serde_json::Value;
 
 use crate::{
     generator::Global,
-    op::{Op, OpFunctionType},
+    op::{Op, OpFormat, OpFunctionType},
 };
 
-type ErrorType = Vec<String>;
+pub(crate) type ErrorType = Vec<String>;
 
 /// This struct is used to serialize the *final* history structure to json, and
 /// parse to Clojure's history data structure.
 ///
 /// We only need to serialize the history, but here implements the Deserialize
 /// trait as well.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Nemesis items whose `f` and `value` are not a client op, e.g. Jepsen's
+/// `{:process :nemesis, :f :start-partition, :value :majority}`, keep them in
+/// [`SerializableHistory::nemesis_op`].
+#[derive(Debug, Clone)]
 pub struct SerializableHistory<F = OpFunctionType, ERR = ErrorType> {
     pub index: u64,
-    #[serde(rename = "type")]
     pub type_: HistoryType,
     pub f: F,
     pub value: Op,
     pub time: u64,
-    pub process: u64,
+    pub process: Process,
     pub error: Option<ERR>,
+    /// Whether the op is read-only, only serialized when it is. Unset unless
+    /// marked by [`SerializableHistoryList::mark_read_only`].
+    pub read_only: bool,
+    /// An id correlating the op with logs outside the history, passed by the
+    /// caller for both the invoke and its result. Never serialized.
+    pub trace_id: Option<u64>,
+    /// Any other keys of the item, e.g. `:debug` in histories recorded by
+    /// Jepsen, kept so that round trips are lossless
+    pub extra: BTreeMap<String, Value>,
+    /// The raw `f` and `value` of a nemesis item that are not a client op.
+    /// `f` and `value` then hold `F::default()` and an empty txn, and these
+    /// are serialized in their place.
+    pub nemesis_op: Option<NemesisOp>,
+}
+
+/// The `f` and `value` of a nemesis item, e.g. `start-partition` and
+/// `majority`, kept as json
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NemesisOp {
+    pub f: Value,
+    pub value: Value,
+}
+
+/// A field of [`HistoryRef`], either typed or the raw json of a nemesis op
+#[derive(Serialize)]
+#[serde(untagged)]
+enum FieldRef<'a, T> {
+    Typed(&'a T),
+    Raw(&'a Value),
+}
+
+/// The serialized shape of a [`SerializableHistory`]
+#[derive(Serialize)]
+struct HistoryRef<'a, F, ERR> {
+    index: u64,
+    #[serde(rename = "type")]
+    type_: &'a HistoryType,
+    f: FieldRef<'a, F>,
+    value: FieldRef<'a, Op>,
+    time: u64,
+    process: Process,
+    error: &'a Option<ERR>,
+    #[serde(rename = "read-only?", skip_serializing_if = "std::ops::Not::not")]
+    read_only: bool,
+    #[serde(flatten)]
+    extra: &'a BTreeMap<String, Value>,
+}
+
+/// The deserialized shape of a [`SerializableHistory`], with `f` and `value`
+/// left as json until the process is known
+#[derive(Deserialize)]
+struct RawHistory<ERR> {
+    index: u64,
+    #[serde(rename = "type")]
+    type_: HistoryType,
+    f: Value,
+    value: Value,
+    time: u64,
+    process: Process,
+    error: Option<ERR>,
+    #[serde(rename = "read-only?", default)]
+    read_only: bool,
+    #[serde(flatten)]
+    extra: BTreeMap<String, Value>,
+}
+
+impl<F: Serialize, ERR: Serialize> Serialize for SerializableHistory<F, ERR> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let (f, value) = match &self.nemesis_op {
+            Some(op) => (FieldRef::Raw(&op.f), FieldRef::Raw(&op.value)),
+            None => (FieldRef::Typed(&self.f), FieldRef::Typed(&self.value)),
+        };
+        HistoryRef {
+            index: self.index,
+            type_: &self.type_,
+            f,
+            value,
+            time: self.time,
+            process: self.process,
+            error: &self.error,
+            read_only: self.read_only,
+            extra: &self.extra,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, F, ERR> Deserialize<'de> for SerializableHistory<F, ERR>
+where
+    F: DeserializeOwned + Default,
+    ERR: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw = RawHistory::<ERR>::deserialize(deserializer)?;
+        let typed = F::deserialize(&raw.f).and_then(|f| Ok((f, Op::deserialize(&raw.value)?)));
+        let (f, value, nemesis_op) = match typed {
+            Ok((f, value)) => (f, value, None),
+            Err(_) if raw.process == Process::Nemesis => (
+                F::default(),
+                Op::Txn(vec![]),
+                Some(NemesisOp {
+                    f: raw.f,
+                    value: raw.value,
+                }),
+            ),
+            Err(e) => return Err(serde::de::Error::custom(e)),
+        };
+        Ok(Self {
+            index: raw.index,
+            type_: raw.type_,
+            f,
+            value,
+            time: raw.time,
+            process: raw.process,
+            error: raw.error,
+            read_only: raw.read_only,
+            trace_id: None,
+            extra: raw.extra,
+            nemesis_op,
+        })
+    }
+}
+
+/// The process of a history item, which is a client id, or the nemesis.
+/// Serialized as a number for clients and `nemesis` for the nemesis, as
+/// Jepsen's `:process :nemesis`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Process {
+    Client(u64),
+    Nemesis,
+}
+
+impl From<u64> for Process {
+    fn from(id: u64) -> Self {
+        Process::Client(id)
+    }
+}
+
+impl Serialize for Process {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Process::Client(id) => serializer.serialize_u64(*id),
+            Process::Nemesis => serializer.serialize_str("nemesis"),
+        }
+    }
+}
+
+/// Temp Struct for [`Process`] deserialization
+struct ProcessVisitor;
+impl<'de> Visitor<'de> for ProcessVisitor {
+    type Value = Process;
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a client id or `nemesis`")
+    }
+    fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Process, E> {
+        Ok(Process::Client(v))
+    }
+    fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Process, E> {
+        u64::try_from(v)
+            .map(Process::Client)
+            .map_err(|_| E::custom(format!("negative client id {v}")))
+    }
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Process, E> {
+        match v.trim_start_matches(':') {
+            "nemesis" => Ok(Process::Nemesis),
+            _ => Err(E::custom(format!("unknown process `{v}`"))),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Process {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ProcessVisitor)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -41,6 +242,7 @@ pub enum HistoryType {
 
 /// A list of Serializable history
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound(deserialize = "SerializableHistory<F, ERR>: Deserialize<'de>"))]
 pub struct SerializableHistoryList<F = OpFunctionType, ERR = ErrorType>(
     pub Vec<SerializableHistory<F, ERR>>,
 );
@@ -63,6 +265,406 @@ impl DerefMut for SerializableHistoryList {
     }
 }
 
+/// A quick performance readout of a history, see
+/// [`SerializableHistoryList::perf_summary`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PerfSummary {
+    /// The time between the first and the last item
+    pub duration: Duration,
+    /// The number of completed (non-invoke) ops
+    pub completed: usize,
+    /// Completed ops per second over `duration`
+    pub ops_per_sec: f64,
+    /// The median time between two consecutive results
+    pub p50_spacing: Duration,
+    /// The 99th percentile time between two consecutive results
+    pub p99_spacing: Duration,
+}
+
+/// The nearest-rank percentile of sorted `values`, 0 if empty
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl<F, ERR> SerializableHistoryList<F, ERR> {
+    /// Summarize the throughput and result spacing from the `:time` of each
+    /// item. Histories with less than 2 items have a zero duration and rate.
+    pub fn perf_summary(&self) -> PerfSummary {
+        let start = self.0.iter().map(|h| h.time).min().unwrap_or(0);
+        let end = self.0.iter().map(|h| h.time).max().unwrap_or(0);
+        let duration = Duration::from_nanos(end - start);
+        let mut results = self
+            .0
+            .iter()
+            .filter(|h| h.type_ != HistoryType::Invoke)
+            .map(|h| h.time)
+            .collect::<Vec<_>>();
+        results.sort_unstable();
+        let mut spacing = results.windows(2).map(|w| w[1] - w[0]).collect::<Vec<_>>();
+        spacing.sort_unstable();
+        let ops_per_sec = if duration.is_zero() {
+            0.0
+        } else {
+            results.len() as f64 / duration.as_secs_f64()
+        };
+        PerfSummary {
+            duration,
+            completed: results.len(),
+            ops_per_sec,
+            p50_spacing: Duration::from_nanos(percentile(&spacing, 0.5)),
+            p99_spacing: Duration::from_nanos(percentile(&spacing, 0.99)),
+        }
+    }
+
+    /// Stably sort the items by `:time` and reindex, e.g. after merging the
+    /// batches of several workers. Items of one worker keep their relative
+    /// order, so each invoke stays before its result.
+    pub fn sort_by_time(&mut self) {
+        self.0.sort_by_key(|item| item.time);
+        self.reindex();
+    }
+
+    /// Set the `read-only?` marker of every read-only client item, for the
+    /// checkers treating read-only txns specially. The marker is opt-in, so
+    /// that histories keep their serialized shape unless asked.
+    pub fn mark_read_only(&mut self) {
+        for item in self.0.iter_mut() {
+            item.read_only = item.process != Process::Nemesis && item.value.is_read_only();
+        }
+    }
+
+    /// Rewrite the indices to be contiguous from 0 in the current order.
+    pub fn reindex(&mut self) {
+        for (index, item) in self.0.iter_mut().enumerate() {
+            item.index = index as u64;
+        }
+    }
+
+    /// Check that the indices are unique and have no gaps from 0. Returns the
+    /// sorted list of duplicated and missing indices otherwise.
+    pub fn check_indices(&self) -> Result<(), Vec<u64>> {
+        let mut counts = BTreeMap::new();
+        for item in &self.0 {
+            *counts.entry(item.index).or_insert(0_usize) += 1;
+        }
+        let max = counts.keys().next_back().map_or(0, |max| max + 1);
+        let bad = (0..max)
+            .filter(|index| counts.get(index).is_none_or(|count| *count != 1))
+            .collect::<Vec<_>>();
+        if bad.is_empty() {
+            Ok(())
+        } else {
+            Err(bad)
+        }
+    }
+
+    /// Map each key to the (min, max) value observed for it, from writes and
+    /// from reads that returned a value. Txns are searched recursively and
+    /// reads of `None` are ignored.
+    pub fn key_value_bounds(&self) -> HashMap<u64, (u64, u64)> {
+        fn visit(op: &Op, bounds: &mut HashMap<u64, (u64, u64)>) {
+            let (key, value) = match op {
+                Op::Read(_, None) => return,
+                Op::Read(key, Some(value)) | Op::Write(key, value) => (*key, *value),
+                Op::Txn(ops) => return ops.iter().for_each(|op| visit(op, bounds)),
+            };
+            let (min, max) = bounds.entry(key).or_insert((value, value));
+            *min = (*min).min(value);
+            *max = (*max).max(value);
+        }
+
+        let mut bounds = HashMap::new();
+        for item in &self.0 {
+            visit(&item.value, &mut bounds);
+        }
+        bounds
+    }
+
+    /// The invokes that have no result yet with their process, in history
+    /// order, e.g. for a watchdog detecting stuck ops.
+    pub fn outstanding(&self) -> Vec<(Process, &SerializableHistory<F, ERR>)> {
+        self.0
+            .iter()
+            .zip(self.pair_positions())
+            .filter(|(item, pair)| item.type_ == HistoryType::Invoke && pair.is_none())
+            .map(|(item, _)| (item.process, item))
+            .collect()
+    }
+
+    /// Find the position of the other half of each invoke/result pair. A
+    /// result pairs with the last unresolved invoke of the same process.
+    fn pair_positions(&self) -> Vec<Option<usize>> {
+        let mut pending = BTreeMap::new();
+        let mut pairs = vec![None; self.0.len()];
+        for (pos, item) in self.0.iter().enumerate() {
+            if item.type_ == HistoryType::Invoke {
+                pending.insert(item.process, pos);
+            } else if let Some(invoke) = pending.remove(&item.process) {
+                pairs[invoke] = Some(pos);
+                pairs[pos] = Some(invoke);
+            }
+        }
+        pairs
+    }
+}
+
+impl<F: Clone, ERR: Clone> SerializableHistoryList<F, ERR> {
+    /// Keep the items matching `pred`, with the indices reassigned to be
+    /// contiguous so that Elle accepts the result. An invoke and its result
+    /// are kept or dropped together: if either one is rejected, both are.
+    pub fn filter_ops(&self, pred: impl Fn(&SerializableHistory<F, ERR>) -> bool) -> Self {
+        let keep = self.0.iter().map(&pred).collect::<Vec<_>>();
+        let pairs = self.pair_positions();
+        let mut list = Self(
+            self.0
+                .iter()
+                .enumerate()
+                .filter(|(pos, _)| keep[*pos] && pairs[*pos].is_none_or(|other| keep[other]))
+                .map(|(_, item)| item.clone())
+                .collect(),
+        );
+        list.reindex();
+        list
+    }
+
+    /// Keep only the ops touching `keys`, e.g. to check a single key quickly.
+    /// Sub-ops of txns on other keys are dropped, and items left with nothing
+    /// are removed together with the other half of their pair. The result is
+    /// reindexed. Read-only markers are kept as they are, call
+    /// [`SerializableHistoryList::mark_read_only`] again to mark txns that
+    /// only read once projected.
+    pub fn project_keys(&self, keys: &HashSet<u64>) -> Self {
+        let mut projected = self.filter_ops(|item| project_op(&item.value, keys).is_some());
+        for item in projected.0.iter_mut() {
+            if let Some(value) = project_op(&item.value, keys) {
+                item.value = value;
+            }
+        }
+        projected
+    }
+
+    /// Split the history into the segments between nemesis events, e.g. for
+    /// before/during/after analysis. A run of consecutive nemesis items, like
+    /// an invoke and its info, counts as one event and is not part of any
+    /// segment, so `n` events give `n + 1` segments, some maybe empty. Each
+    /// segment is reindexed; an op in flight across an event has its invoke
+    /// and result in different segments.
+    pub fn split_at_nemesis(&self) -> Vec<Self> {
+        let mut segments = vec![Self(vec![])];
+        let mut in_event = false;
+        for item in &self.0 {
+            if item.process == Process::Nemesis {
+                if !in_event {
+                    segments.push(Self(vec![]));
+                }
+                in_event = true;
+            } else {
+                in_event = false;
+                if let Some(segment) = segments.last_mut() {
+                    segment.0.push(item.clone());
+                }
+            }
+        }
+        segments.iter_mut().for_each(Self::reindex);
+        segments
+    }
+}
+
+/// The part of `op` touching `keys`, `None` if there is none
+fn project_op(op: &Op, keys: &HashSet<u64>) -> Option<Op> {
+    match op {
+        Op::Read(key, _) | Op::Write(key, _) => keys.contains(key).then(|| op.clone()),
+        Op::Txn(ops) => {
+            let ops = ops
+                .iter()
+                .filter_map(|op| project_op(op, keys))
+                .collect::<Vec<_>>();
+            (!ops.is_empty()).then_some(Op::Txn(ops))
+        }
+    }
+}
+
+/// The approximate heap bytes owned by an [`Op`]
+fn op_heap_bytes(op: &Op) -> usize {
+    match op {
+        Op::Read(_, _) | Op::Write(_, _) => 0,
+        Op::Txn(ops) => {
+            ops.capacity() * size_of::<Op>() + ops.iter().map(op_heap_bytes).sum::<usize>()
+        }
+    }
+}
+
+/// The approximate heap bytes owned by a json object entry
+fn json_entry_bytes(key: &String, value: &Value) -> usize {
+    size_of::<(String, Value)>() + key.capacity() + json_heap_bytes(value)
+}
+
+/// The approximate heap bytes owned by a json [`Value`]
+fn json_heap_bytes(value: &Value) -> usize {
+    match value {
+        Value::Null | Value::Bool(_) | Value::Number(_) => 0,
+        Value::String(s) => s.capacity(),
+        Value::Array(values) => {
+            values.capacity() * size_of::<Value>()
+                + values.iter().map(json_heap_bytes).sum::<usize>()
+        }
+        Value::Object(map) => map.iter().map(|(k, v)| json_entry_bytes(k, v)).sum(),
+    }
+}
+
+impl<F> SerializableHistoryList<F, ErrorType> {
+    /// Roughly estimate the memory held by the history, including the ops in
+    /// txns, the error strings, the `extra` fields and the raw nemesis ops. It
+    /// is not exact, as allocator overhead is ignored.
+    pub fn estimated_bytes(&self) -> usize {
+        let items = self.0.capacity() * size_of::<SerializableHistory<F, ErrorType>>();
+        let heap = self
+            .0
+            .iter()
+            .map(|item| {
+                let error = item.error.as_ref().map_or(0, |errors| {
+                    errors.capacity() * size_of::<String>()
+                        + errors.iter().map(String::capacity).sum::<usize>()
+                });
+                let extra = item
+                    .extra
+                    .iter()
+                    .map(|(k, v)| json_entry_bytes(k, v))
+                    .sum::<usize>();
+                let nemesis_op = item
+                    .nemesis_op
+                    .as_ref()
+                    .map_or(0, |op| json_heap_bytes(&op.f) + json_heap_bytes(&op.value));
+                op_heap_bytes(&item.value) + error + extra + nemesis_op
+            })
+            .sum::<usize>();
+        size_of::<Self>() + items + heap
+    }
+}
+
+impl<F: Serialize, ERR: Serialize> SerializableHistoryList<F, ERR> {
+    /// Write the history as json lines, one history item per line.
+    pub fn write_jsonl(&self, mut w: impl Write) -> anyhow::Result<()> {
+        for item in &self.0 {
+            serde_json::to_writer(&mut w, item)?;
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl<ERR: Serialize> SerializableHistoryList<OpFunctionType, ERR> {
+    /// Convert the history to json with `f` and `value` in `format`, e.g. for
+    /// a store naming its micro-ops `get`/`set`. The other fields are as the
+    /// `Serialize` impl emits them.
+    pub fn to_json_with(&self, format: &OpFormat) -> serde_json::Result<Value> {
+        self.0
+            .iter()
+            .map(|item| {
+                let mut json = serde_json::to_value(item)?;
+                if item.nemesis_op.is_none() {
+                    json["f"] = format.f_tag(&item.f).into();
+                    json["value"] = format.to_json(&item.value);
+                }
+                Ok(json)
+            })
+            .collect::<serde_json::Result<_>>()
+            .map(Value::Array)
+    }
+}
+
+impl<F: DeserializeOwned + Default, ERR: DeserializeOwned> SerializableHistoryList<F, ERR> {
+    /// Read a history written by [`SerializableHistoryList::write_jsonl`].
+    /// Blank lines are skipped.
+    pub fn read_jsonl(r: impl Read) -> anyhow::Result<Self> {
+        let mut list = vec![];
+        for line in BufReader::new(r).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            list.push(serde_json::from_str(&line)?);
+        }
+        Ok(Self(list))
+    }
+}
+
+/// Clojure fn writing a json history to a fressian file with Jepsen's
+/// handlers, so that `jepsen.store.fressian` can load it back in a Jepsen
+/// REPL. Keys, `:type`, `:f`, a `:nemesis` process and micro-op tags such as
+/// `[:r 1 nil]` are keywordized first, as Jepsen ops expect.
+#[cfg(feature = "jvm")]
+use crate::ffi::with_cached_fn;
+
+#[cfg(feature = "jvm")]
+const WRITE_FRESSIAN_FN: &str = r#"
+(require 'clojure.java.io 'clojure.data.json 'clojure.walk 'jepsen.history
+         'jepsen.store.fressian 'clojure.data.fressian)
+(fn [json path]
+  (let [mop   (fn [x] (if (and (vector? x) (string? (first x))) (update x 0 keyword) x))
+        op    (fn [o]
+                (cond-> (-> o (update :type keyword) (update :f keyword)
+                            (update :value (partial clojure.walk/postwalk mop)))
+                  (string? (:process o)) (update :process keyword)))
+        ops   (mapv op (clojure.data.json/read-str json :key-fn keyword))]
+    (with-open [os (clojure.java.io/output-stream path)
+                w  (jepsen.store.fressian/writer os)]
+      (clojure.data.fressian/write-object w (jepsen.history/history ops)))))
+"#;
+
+/// Clojure fn reading a history written by [`WRITE_FRESSIAN_FN`], turning the
+/// `jepsen.history.Op` records back into plain maps.
+#[cfg(feature = "jvm")]
+const READ_FRESSIAN_FN: &str = r#"
+(require 'clojure.java.io 'jepsen.store.fressian 'clojure.data.fressian)
+(fn [path]
+  (with-open [is (clojure.java.io/input-stream path)
+              r  (jepsen.store.fressian/reader is)]
+    (mapv (partial into {}) (clojure.data.fressian/read-object r))))
+"#;
+
+#[cfg(feature = "jvm")]
+thread_local! {
+    static WRITE_FRESSIAN: OnceCell<crate::IFn> = const { OnceCell::new() };
+    static READ_FRESSIAN: OnceCell<crate::IFn> = const { OnceCell::new() };
+}
+
+#[cfg(feature = "jvm")]
+impl<F: Serialize + DeserializeOwned + Default, ERR: Serialize + DeserializeOwned>
+    SerializableHistoryList<F, ERR>
+{
+    /// Historify the list and write it to `path` in Jepsen's fressian format.
+    pub fn to_fressian(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        use j4rs::InvocationArg;
+
+        let json = serde_json::to_string(self)?;
+        let path = path.as_ref().to_string_lossy().into_owned();
+        with_cached_fn(&WRITE_FRESSIAN, WRITE_FRESSIAN_FN, |write| {
+            write.invoke(&[
+                InvocationArg::try_from(json)?,
+                InvocationArg::try_from(path)?,
+            ])?;
+            Ok(())
+        })
+    }
+
+    /// Read a history written by [`SerializableHistoryList::to_fressian`].
+    pub fn from_fressian(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        use j4rs::InvocationArg;
+
+        use crate::utils::ToDe;
+
+        let path = path.as_ref().to_string_lossy().into_owned();
+        with_cached_fn(&READ_FRESSIAN, READ_FRESSIAN_FN, |read| {
+            read.invoke1(InvocationArg::try_from(path)?)?.to_de()
+        })
+    }
+}
+
 impl<ERR> SerializableHistoryList<OpFunctionType, ERR> {
     /// Get the current timestamp.
     fn timestamp(&self, global: &Arc<Global>) -> u64 {
@@ -71,7 +673,29 @@ impl<ERR> SerializableHistoryList<OpFunctionType, ERR> {
             .as_nanos() as u64
     }
     /// Push an invoke history to the history list.
-    pub fn push_invoke(&mut self, global: &Arc<Global>, process: u64, value: Op) {
+    pub fn push_invoke(&mut self, global: &Arc<Global>, process: impl Into<Process>, value: Op) {
+        self.push_invoke_inner(global, process.into(), value, None);
+    }
+
+    /// Push an invoke history carrying `trace_id`. Pass the same id to
+    /// [`SerializableHistoryList::push_result_traced`] for its result.
+    pub fn push_invoke_traced(
+        &mut self,
+        global: &Arc<Global>,
+        process: impl Into<Process>,
+        value: Op,
+        trace_id: u64,
+    ) {
+        self.push_invoke_inner(global, process.into(), value, Some(trace_id));
+    }
+
+    fn push_invoke_inner(
+        &mut self,
+        global: &Arc<Global>,
+        process: Process,
+        value: Op,
+        trace_id: Option<u64>,
+    ) {
         let f: OpFunctionType = (&value).into();
         let item = SerializableHistory {
             index: self.0.len() as u64,
@@ -81,6 +705,10 @@ impl<ERR> SerializableHistoryList<OpFunctionType, ERR> {
             time: self.timestamp(global),
             process,
             error: None,
+            read_only: false,
+            trace_id,
+            extra: BTreeMap::new(),
+            nemesis_op: None,
         };
         self.0.push(item);
     }
@@ -89,10 +717,43 @@ impl<ERR> SerializableHistoryList<OpFunctionType, ERR> {
     pub fn push_result(
         &mut self,
         global: &Arc<Global>,
-        process: u64,
+        process: impl Into<Process>,
+        result_type: HistoryType,
+        value: Op,
+        error: Option<ERR>,
+    ) {
+        self.push_result_inner(global, process.into(), result_type, value, error, None);
+    }
+
+    /// Push a result carrying the `trace_id` of its invoke, see
+    /// [`SerializableHistoryList::push_invoke_traced`].
+    pub fn push_result_traced(
+        &mut self,
+        global: &Arc<Global>,
+        process: impl Into<Process>,
         result_type: HistoryType,
         value: Op,
         error: Option<ERR>,
+        trace_id: u64,
+    ) {
+        self.push_result_inner(
+            global,
+            process.into(),
+            result_type,
+            value,
+            error,
+            Some(trace_id),
+        );
+    }
+
+    fn push_result_inner(
+        &mut self,
+        global: &Arc<Global>,
+        process: Process,
+        result_type: HistoryType,
+        value: Op,
+        error: Option<ERR>,
+        trace_id: Option<u64>,
     ) {
         assert!(
             (result_type == HistoryType::Ok) == (error.is_none()),
@@ -107,28 +768,762 @@ impl<ERR> SerializableHistoryList<OpFunctionType, ERR> {
             time: self.timestamp(global),
             process,
             error,
+            read_only: false,
+            trace_id,
+            extra: BTreeMap::new(),
+            nemesis_op: None,
         };
         self.0.push(item);
     }
 }
 
+/// Builds a [`SerializableHistoryList`] item by item, mainly for tests. The
+/// index is the position of the item, and the time starts at 0 and grows by
+/// 1 per item.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryBuilder {
+    list: SerializableHistoryList,
+}
+
+impl HistoryBuilder {
+    /// Create an empty builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an `:invoke` of `op` by `process`
+    pub fn invoke(self, process: impl Into<Process>, op: Op) -> Self {
+        self.push(HistoryType::Invoke, process.into(), op, None)
+    }
+
+    /// Append an `:ok` of `op` by `process`
+    pub fn ok(self, process: impl Into<Process>, op: Op) -> Self {
+        self.push(HistoryType::Ok, process.into(), op, None)
+    }
+
+    /// Append a `:fail` of `op` by `process` with the error `err`
+    pub fn fail(self, process: impl Into<Process>, op: Op, err: ErrorType) -> Self {
+        self.push(HistoryType::Fail, process.into(), op, Some(err))
+    }
+
+    /// Append an `:info` of `op` by `process`
+    pub fn info(self, process: impl Into<Process>, op: Op) -> Self {
+        self.push(HistoryType::Info, process.into(), op, None)
+    }
+
+    fn push(
+        mut self,
+        type_: HistoryType,
+        process: Process,
+        value: Op,
+        error: Option<ErrorType>,
+    ) -> Self {
+        let index = self.list.len() as u64;
+        self.list.push(SerializableHistory {
+            index,
+            type_,
+            f: (&value).into(),
+            read_only: false,
+            value,
+            time: index,
+            process,
+            error,
+            trace_id: None,
+            extra: BTreeMap::new(),
+            nemesis_op: None,
+        });
+        self
+    }
+
+    /// Finish building
+    pub fn build(self) -> SerializableHistoryList {
+        self.list
+    }
+}
+
+/// A destination of history items. The in-memory [`SerializableHistoryList`]
+/// is the default, other sinks can stream the items elsewhere (files, message
+/// queues, ...) during long runs. Set one with [`Global::with_history_sink`],
+/// and record through [`Global::record_invoke`] and [`Global::record_result`].
+pub trait HistorySink<ERR = ErrorType> {
+    /// Record an invoke, `trace_id` is set for the traced ones
+    fn record_invoke(
+        &mut self,
+        global: &Arc<Global>,
+        process: Process,
+        value: Op,
+        trace_id: Option<u64>,
+    );
+
+    /// Record a result, carrying the `trace_id` of its invoke
+    fn record_result(
+        &mut self,
+        global: &Arc<Global>,
+        process: Process,
+        result_type: HistoryType,
+        value: Op,
+        error: Option<ERR>,
+        trace_id: Option<u64>,
+    );
+}
+
+impl<ERR> HistorySink<ERR> for SerializableHistoryList<OpFunctionType, ERR> {
+    fn record_invoke(
+        &mut self,
+        global: &Arc<Global>,
+        process: Process,
+        value: Op,
+        trace_id: Option<u64>,
+    ) {
+        self.push_invoke_inner(global, process, value, trace_id);
+    }
+
+    fn record_result(
+        &mut self,
+        global: &Arc<Global>,
+        process: Process,
+        result_type: HistoryType,
+        value: Op,
+        error: Option<ERR>,
+        trace_id: Option<u64>,
+    ) {
+        self.push_result_inner(global, process, result_type, value, error, trace_id);
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use j4rs::Instance;
-
     use super::*;
-    use crate::{
-        read_edn,
-        utils::{print_clj, FromSerde, ToDe},
-    };
+    use crate::generator::CyclicOpGenerator;
 
+    #[cfg(feature = "jvm")]
     #[test]
     fn test_history_list_conversion() -> anyhow::Result<()> {
+        use crate::ClojureValue;
+
+        use crate::{
+            read_edn,
+            utils::{print_clj, FromSerde, ToDe},
+        };
+
         let his_edn = read_edn(include_str!("../assets/ex_history.edn"))?;
         let res: SerializableHistoryList = his_edn.to_de()?;
         assert_eq!(res.len(), 4);
-        let res: Instance = Instance::from_ser(res)?;
+        let res = ClojureValue::from_ser(res)?;
         print_clj(res);
         Ok(())
     }
+
+    #[cfg(feature = "jvm")]
+    #[test]
+    fn test_fressian_round_trip() -> anyhow::Result<()> {
+        use j4rs::InvocationArg;
+
+        use crate::{
+            read_edn,
+            utils::{clj_to_string, FromSerde, ToDe},
+            ClojureValue, IFn,
+        };
+
+        let edn = include_str!("../assets/ex_history.edn");
+        let his: SerializableHistoryList = read_edn(edn)?.to_de()?;
+        let path = std::env::temp_dir().join(format!(
+            "jepsen-rs-fressian-round-trip-{}.fressian",
+            std::process::id()
+        ));
+        his.to_fressian(&path)?;
+        // the second write goes through the fn cached by the first
+        his.to_fressian(&path)?;
+        let raw = with_cached_fn(&READ_FRESSIAN, READ_FRESSIAN_FN, |read| {
+            read.invoke1(InvocationArg::try_from(path.to_string_lossy().as_ref())?)
+        })?;
+        let back = SerializableHistoryList::<OpFunctionType>::from_fressian(&path)?;
+        std::fs::remove_file(&path)?;
+
+        // the ops read back are the keywordized ops of the original edn
+        let same_ops = IFn::new(read_edn(
+            "(fn [a b] (let [ks [:index :time :type :process :f :value]] \
+               (= (map #(select-keys % ks) a) (map #(select-keys % ks) b))))",
+        )?);
+        let eq = same_ops.invoke(&[
+            InvocationArg::from(raw),
+            InvocationArg::from(read_edn(edn)?),
+        ])?;
+        assert_eq!(clj_to_string(eq)?, "true");
+
+        let (his, back) = (
+            ClojureValue::from_ser(&his)?,
+            ClojureValue::from_ser(&back)?,
+        );
+        let eq = cljinvoke!("=", his, back)?;
+        assert_eq!(clj_to_string(eq)?, "true");
+        Ok(())
+    }
+
+    #[cfg(feature = "jvm")]
+    #[test]
+    fn test_mixed_process_history() -> anyhow::Result<()> {
+        use crate::{read_edn, utils::ToDe};
+
+        let his: SerializableHistoryList =
+            read_edn(include_str!("../assets/ex_history_nemesis.edn"))?.to_de()?;
+        let processes = his.iter().map(|item| item.process).collect::<Vec<_>>();
+        assert_eq!(
+            processes,
+            vec![
+                Process::Client(0),
+                Process::Nemesis,
+                Process::Client(1),
+                Process::Nemesis,
+                Process::Client(0),
+                Process::Client(1),
+            ]
+        );
+        assert_eq!(his.split_at_nemesis().len(), 2);
+        let nemesis_op = his[1].nemesis_op.as_ref().unwrap();
+        assert_eq!(nemesis_op.f, Value::from("start-partition"));
+        assert_eq!(nemesis_op.value, Value::from("majority"));
+        assert!(his[3].nemesis_op.is_some());
+        assert!(his[0].nemesis_op.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_nemesis_op_serde() -> anyhow::Result<()> {
+        let json = r#"{"index":1,"type":"info","f":"start-partition","value":["isolated",{"n1":["n2","n3"]}],"time":150,"process":"nemesis","error":null}"#;
+        let item: SerializableHistory = serde_json::from_str(json)?;
+        assert_eq!(item.process, Process::Nemesis);
+        assert_eq!(item.value, Op::Txn(vec![]));
+        assert_eq!(
+            item.nemesis_op.as_ref().unwrap().f,
+            Value::from("start-partition")
+        );
+        assert_eq!(serde_json::to_string(&item)?, json);
+
+        // a nemesis item shaped like a client op stays typed
+        let json = r#"{"index":1,"type":"info","f":"txn","value":[],"time":150,"process":"nemesis","error":null}"#;
+        let item: SerializableHistory = serde_json::from_str(json)?;
+        assert!(item.nemesis_op.is_none());
+
+        let json = r#"{"index":1,"type":"info","f":"start-partition","value":null,"time":150,"process":0,"error":null}"#;
+        assert!(serde_json::from_str::<SerializableHistory>(json).is_err());
+        Ok(())
+    }
+
+    fn item(index: u64, type_: HistoryType) -> SerializableHistory {
+        SerializableHistory {
+            index,
+            type_,
+            f: OpFunctionType::Read,
+            value: Op::Read(1, None),
+            time: index,
+            process: Process::Client(0),
+            error: None,
+            read_only: true,
+            trace_id: None,
+            extra: BTreeMap::new(),
+            nemesis_op: None,
+        }
+    }
+
+    #[test]
+    fn test_check_indices_and_reindex() {
+        let mut his = SerializableHistoryList(vec![
+            item(0, HistoryType::Invoke),
+            item(2, HistoryType::Ok),
+            item(2, HistoryType::Invoke),
+            item(4, HistoryType::Ok),
+        ]);
+        assert_eq!(his.check_indices(), Err(vec![1, 2, 3]));
+
+        his.reindex();
+        assert_eq!(his.check_indices(), Ok(()));
+        assert_eq!(
+            his.iter().map(|h| h.index).collect::<Vec<_>>(),
+            [0, 1, 2, 3]
+        );
+        assert_eq!(his[2].type_, HistoryType::Invoke);
+
+        assert_eq!(SerializableHistoryList::default().check_indices(), Ok(()));
+    }
+
+    #[test]
+    fn test_filter_ops() {
+        let at = |process, type_| SerializableHistory {
+            process: Process::Client(process),
+            ..item(0, type_)
+        };
+        let mut his = SerializableHistoryList(vec![
+            at(0, HistoryType::Invoke),
+            at(1, HistoryType::Invoke),
+            at(0, HistoryType::Ok),
+            at(2, HistoryType::Invoke),
+            at(1, HistoryType::Info),
+            at(2, HistoryType::Fail),
+            at(0, HistoryType::Invoke),
+        ]);
+        his.reindex();
+
+        let res = his.filter_ops(|h| h.type_ != HistoryType::Info);
+        assert_eq!(res.check_indices(), Ok(()));
+        let shape = res
+            .iter()
+            .map(|h| (h.process, h.type_.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shape,
+            vec![
+                (Process::Client(0), HistoryType::Invoke),
+                (Process::Client(0), HistoryType::Ok),
+                (Process::Client(2), HistoryType::Invoke),
+                (Process::Client(2), HistoryType::Fail),
+                (Process::Client(0), HistoryType::Invoke),
+            ]
+        );
+
+        let res = his.filter_ops(|h| h.process == Process::Client(2));
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[1].index, 1);
+    }
+
+    #[test]
+    fn test_key_value_bounds() {
+        let mut his = SerializableHistoryList::default();
+        for (type_, value) in [
+            (HistoryType::Invoke, Op::Write(1, 5)),
+            (HistoryType::Ok, Op::Write(1, 5)),
+            (HistoryType::Invoke, Op::Read(1, None)),
+            (HistoryType::Ok, Op::Read(1, Some(2))),
+            (
+                HistoryType::Invoke,
+                Op::Txn(vec![Op::Write(2, 7), Op::Txn(vec![Op::Write(1, 9)])]),
+            ),
+            (HistoryType::Invoke, Op::Read(3, None)),
+        ] {
+            let index = his.len() as u64;
+            his.push(SerializableHistory {
+                value,
+                ..item(index, type_)
+            });
+        }
+        assert_eq!(
+            his.key_value_bounds(),
+            HashMap::from([(1, (2, 9)), (2, (7, 7))])
+        );
+    }
+
+    #[test]
+    fn test_project_keys() {
+        let txn = Op::Txn(vec![
+            Op::Write(1, 1),
+            Op::Txn(vec![Op::Read(2, None), Op::Write(2, 2)]),
+        ]);
+        let his = HistoryBuilder::new()
+            .invoke(0, txn.clone())
+            .invoke(1, Op::Write(1, 3))
+            .ok(0, txn)
+            .ok(1, Op::Write(1, 3))
+            .invoke(0, Op::Read(2, None))
+            .ok(0, Op::Read(2, Some(2)))
+            .build();
+
+        let projected = his.project_keys(&HashSet::from([2]));
+        assert_eq!(projected.check_indices(), Ok(()));
+        let values = projected
+            .iter()
+            .map(|item| item.value.clone())
+            .collect::<Vec<_>>();
+        let sub = Op::Txn(vec![Op::Txn(vec![Op::Read(2, None), Op::Write(2, 2)])]);
+        assert_eq!(
+            values,
+            vec![sub.clone(), sub, Op::Read(2, None), Op::Read(2, Some(2))]
+        );
+        assert!(!projected[0].read_only);
+
+        let projected = his.project_keys(&HashSet::from([1]));
+        assert_eq!(projected.len(), 4);
+        assert!(projected.iter().all(|item| item.value.keys() == vec![1]));
+    }
+
+    #[test]
+    fn test_split_at_nemesis() {
+        let his = HistoryBuilder::new()
+            .invoke(0, Op::Write(1, 1))
+            .ok(0, Op::Write(1, 1))
+            .invoke(Process::Nemesis, Op::Read(0, None))
+            .info(Process::Nemesis, Op::Read(0, None))
+            .invoke(1, Op::Read(1, None))
+            .ok(1, Op::Read(1, Some(1)))
+            .invoke(0, Op::Write(1, 2))
+            .ok(0, Op::Write(1, 2))
+            .build();
+        let segments = his.split_at_nemesis();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0].len(), 2);
+        assert_eq!(segments[1].len(), 4);
+        for segment in &segments {
+            assert_eq!(segment.check_indices(), Ok(()));
+            assert!(segment.iter().all(|item| item.process != Process::Nemesis));
+        }
+        assert_eq!(segments[1][0].value, Op::Read(1, None));
+
+        assert_eq!(HistoryBuilder::new().build().split_at_nemesis().len(), 1);
+    }
+
+    #[test]
+    fn test_estimated_bytes() {
+        let empty = SerializableHistoryList::default().estimated_bytes();
+        let mut his = SerializableHistoryList::default();
+        let mut last = empty;
+        for i in 0..10 {
+            his.push(SerializableHistory {
+                value: Op::Txn(vec![Op::Write(i, i), Op::Read(i, None)]),
+                error: Some(vec!["timeout".to_string()]),
+                ..item(i, HistoryType::Fail)
+            });
+            let bytes = his.estimated_bytes();
+            assert!(bytes > last);
+            last = bytes;
+        }
+        assert!(last >= empty + 10 * (2 * size_of::<Op>() + "timeout".len()));
+
+        let note = "a".repeat(1000);
+        his[0]
+            .extra
+            .insert("node".to_string(), serde_json::json!({ "note": note }));
+        assert!(his.estimated_bytes() >= last + "node".len() + "note".len() + note.len());
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_history_sink() {
+        use std::{cell::Cell, rc::Rc};
+
+        #[derive(Default)]
+        struct CountingSink {
+            invokes: Rc<Cell<usize>>,
+            traced: Rc<Cell<usize>>,
+        }
+
+        impl HistorySink for CountingSink {
+            fn record_invoke(&mut self, _: &Arc<Global>, _: Process, _: Op, trace_id: Option<u64>) {
+                self.invokes.set(self.invokes.get() + 1);
+                self.traced
+                    .set(self.traced.get() + trace_id.is_some() as usize);
+            }
+
+            fn record_result(
+                &mut self,
+                _: &Arc<Global>,
+                _: Process,
+                _: HistoryType,
+                _: Op,
+                _: Option<ErrorType>,
+                trace_id: Option<u64>,
+            ) {
+                self.traced
+                    .set(self.traced.get() + trace_id.is_some() as usize);
+            }
+        }
+
+        fn record(global: &Arc<Global>) {
+            for process in 0..3 {
+                global.record_invoke(process, Op::Read(1, None), Some(process));
+                global.record_result(
+                    process,
+                    HistoryType::Ok,
+                    Op::Read(1, Some(1)),
+                    None,
+                    Some(process),
+                );
+            }
+            global.record_invoke(0, Op::Write(1, 2), None);
+        }
+
+        madsim::runtime::Runtime::new().block_on(async {
+            let gen = Arc::new(CyclicOpGenerator::new(vec![Op::Read(1, None)]));
+            let counting = CountingSink::default();
+            let (invokes, traced) = (counting.invokes.clone(), counting.traced.clone());
+            let global = Arc::new(Global::new(gen.clone()).with_history_sink(Box::new(counting)));
+            record(&global);
+            assert_eq!((invokes.get(), traced.get()), (4, 6));
+            assert!(global.history.lock().unwrap().is_empty());
+
+            let global = Arc::new(Global::new(gen));
+            record(&global);
+            let history = global.history.lock().unwrap();
+            assert_eq!(history.len(), 7);
+            assert_eq!(history.check_indices(), Ok(()));
+            assert_eq!(history[1].trace_id, Some(0));
+            assert_eq!(history[6].trace_id, None);
+        });
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_trace_id() -> anyhow::Result<()> {
+        madsim::runtime::Runtime::new().block_on(async {
+            let gen = CyclicOpGenerator::new(vec![Op::Read(1, None)]);
+            let global = Arc::new(Global::new(Arc::new(gen)));
+            let mut his = SerializableHistoryList::<OpFunctionType>::default();
+            his.push_invoke_traced(&global, 0, Op::Write(1, 1), 7);
+            his.push_invoke(&global, 1, Op::Read(1, None));
+            his.push_result_traced(&global, 0, HistoryType::Ok, Op::Write(1, 1), None, 7);
+            his.push_result(&global, 1, HistoryType::Ok, Op::Read(1, Some(1)), None);
+            his.push_result(&global, 0, HistoryType::Ok, Op::Write(1, 2), None);
+
+            let trace_ids = his.iter().map(|item| item.trace_id).collect::<Vec<_>>();
+            assert_eq!(trace_ids, vec![Some(7), None, Some(7), None, None]);
+            assert!(!serde_json::to_string(&his)?.contains("trace"));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_history_builder() -> anyhow::Result<()> {
+        let his = HistoryBuilder::new()
+            .invoke(0, Op::Write(1, 1))
+            .invoke(Process::Nemesis, Op::Read(2, None))
+            .ok(0, Op::Write(1, 1))
+            .invoke(1, Op::Read(1, None))
+            .fail(1, Op::Read(1, None), vec!["timeout".to_string()])
+            .info(Process::Nemesis, Op::Read(2, None))
+            .build();
+
+        assert_eq!(his.len(), 6);
+        assert_eq!(his.check_indices(), Ok(()));
+        assert_eq!(
+            his.iter().map(|item| item.time).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            his.iter()
+                .map(|item| item.type_.clone())
+                .collect::<Vec<_>>(),
+            vec![
+                HistoryType::Invoke,
+                HistoryType::Invoke,
+                HistoryType::Ok,
+                HistoryType::Invoke,
+                HistoryType::Fail,
+                HistoryType::Info,
+            ]
+        );
+        assert_eq!(his[1].process, Process::Nemesis);
+        assert!(his.iter().all(|item| !item.read_only));
+        assert_eq!(his[4].error, Some(vec!["timeout".to_string()]));
+        assert!(his
+            .iter()
+            .filter(|item| item.index != 4)
+            .all(|item| item.error.is_none()));
+        assert_eq!(serde_json::to_value(&his[2])?["f"], "w");
+        Ok(())
+    }
+
+    #[test]
+    fn test_outstanding() {
+        let his = HistoryBuilder::new()
+            .invoke(0, Op::Write(1, 1))
+            .invoke(1, Op::Read(1, None))
+            .ok(0, Op::Write(1, 1))
+            .build();
+        let outstanding = his.outstanding();
+        assert_eq!(outstanding.len(), 1);
+        assert_eq!(outstanding[0].0, Process::Client(1));
+        assert_eq!(outstanding[0].1.index, 1);
+    }
+
+    #[test]
+    fn test_perf_summary() {
+        let ms = Duration::from_millis(1).as_nanos() as u64;
+        let at = |time, type_| SerializableHistory {
+            time: time * ms,
+            ..item(0, type_)
+        };
+        let mut his = SerializableHistoryList(vec![at(0, HistoryType::Invoke)]);
+        // results at 10ms, 20ms, ..., 1000ms, spaced by 10ms except a 100ms gap
+        for i in 1..=100 {
+            his.push(at(i * 10, HistoryType::Invoke));
+            his.push(at(i * 10 + if i == 100 { 90 } else { 0 }, HistoryType::Ok));
+        }
+        let perf = his.perf_summary();
+        assert_eq!(perf.duration, Duration::from_millis(1090));
+        assert_eq!(perf.completed, 100);
+        assert!((perf.ops_per_sec - 100.0 / 1.09).abs() < 1e-9);
+        assert_eq!(perf.p50_spacing, Duration::from_millis(10));
+        assert_eq!(perf.p99_spacing, Duration::from_millis(100));
+
+        let perf = SerializableHistoryList(vec![at(5, HistoryType::Ok)]).perf_summary();
+        assert_eq!(perf.duration, Duration::ZERO);
+        assert_eq!(perf.completed, 1);
+        assert_eq!(perf.ops_per_sec, 0.0);
+        assert_eq!(perf.p99_spacing, Duration::ZERO);
+        assert_eq!(
+            SerializableHistoryList::default().perf_summary().completed,
+            0
+        );
+    }
+
+    #[test]
+    fn test_history_list_jsonl_round_trip() -> anyhow::Result<()> {
+        let his = SerializableHistoryList(vec![
+            SerializableHistory {
+                index: 0,
+                type_: HistoryType::Invoke,
+                f: OpFunctionType::Txn,
+                value: Op::Txn(vec![Op::Write(1, 2), Op::Read(3, None)]),
+                time: 10,
+                process: Process::Client(0),
+                error: None,
+                read_only: false,
+                trace_id: None,
+                extra: BTreeMap::new(),
+                nemesis_op: None,
+            },
+            SerializableHistory {
+                index: 1,
+                type_: HistoryType::Fail,
+                f: OpFunctionType::Txn,
+                value: Op::Txn(vec![Op::Write(1, 2), Op::Read(3, None)]),
+                time: 20,
+                process: Process::Client(0),
+                error: Some(vec!["timeout".to_string()]),
+                read_only: false,
+                trace_id: None,
+                extra: BTreeMap::new(),
+                nemesis_op: None,
+            },
+        ]);
+        let mut buf = vec![];
+        his.write_jsonl(&mut buf)?;
+        let text = String::from_utf8(buf.clone())?;
+        assert_eq!(text.lines().count(), 2);
+
+        let res: SerializableHistoryList = SerializableHistoryList::read_jsonl(&buf[..])?;
+        assert_eq!(serde_json::to_string(&res)?, serde_json::to_string(&his)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_json_with_format() -> anyhow::Result<()> {
+        use serde_json::json;
+
+        use crate::op::OpTags;
+
+        let his = HistoryBuilder::new()
+            .invoke(0, Op::Read(1, None))
+            .ok(0, Op::Read(1, Some(2)))
+            .invoke(1, Op::Txn(vec![Op::Write(1, 3), Op::Read(2, None)]))
+            .build();
+        let format = OpFormat::default().with_tags(OpTags {
+            read: "get".into(),
+            write: "set".into(),
+            txn: "multi".into(),
+        });
+        let json = his.to_json_with(&format)?;
+        let fs = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| &item["f"])
+            .collect::<Vec<_>>();
+        assert_eq!(fs, [&json!("get"), &json!("get"), &json!("multi")]);
+        assert_eq!(json[1]["value"], json!(["get", 1, 2]));
+        assert_eq!(json[2]["value"], json!([["set", 1, 3], ["get", 2, null]]));
+        assert_eq!(json[2]["index"], json!(2));
+
+        assert_eq!(
+            his.to_json_with(&OpFormat::default())?,
+            serde_json::to_value(&his)?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_json_with_none_read() -> anyhow::Result<()> {
+        use serde_json::json;
+
+        use crate::op::NoneRead;
+
+        let his = HistoryBuilder::new()
+            .invoke(0, Op::Txn(vec![Op::Read(1, None), Op::Write(2, 1)]))
+            .ok(0, Op::Txn(vec![Op::Read(1, Some(4)), Op::Write(2, 1)]))
+            .build();
+        let format = OpFormat::default().with_none_read(NoneRead::Absent);
+        let json = his.to_json_with(&format)?;
+        assert_eq!(json[0]["value"], json!([["r", 1], ["w", 2, 1]]));
+        assert_eq!(json[1]["value"], json!([["r", 1, 4], ["w", 2, 1]]));
+
+        let back: SerializableHistoryList = serde_json::from_value(json)?;
+        assert_eq!(back[0].value, his[0].value);
+        Ok(())
+    }
+
+    #[test]
+    fn test_extra_fields_round_trip() -> anyhow::Result<()> {
+        let json = r#"{"index":0,"type":"invoke","f":"r","value":["r",1,null],"time":0,"process":0,"error":null,"debug":{"node":"n1"},"retry?":true}"#;
+        let res: SerializableHistory = serde_json::from_str(json)?;
+        assert_eq!(res.extra.len(), 2);
+        assert_eq!(res.extra["retry?"], Value::Bool(true));
+        assert_eq!(res.value, Op::Read(1, None));
+
+        let back = serde_json::to_value(&res)?;
+        assert_eq!(back, serde_json::from_str::<Value>(json)?);
+        assert!(serde_json::to_value(item(0, HistoryType::Ok))?
+            .as_object()
+            .is_some_and(|obj| obj.len() == 8));
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_serde() -> anyhow::Result<()> {
+        assert_eq!(serde_json::to_string(&Process::Client(3))?, "3");
+        assert_eq!(serde_json::to_string(&Process::Nemesis)?, r#""nemesis""#);
+        for (json, process) in [
+            ("3", Process::Client(3)),
+            (r#""nemesis""#, Process::Nemesis),
+            (r#"":nemesis""#, Process::Nemesis),
+        ] {
+            assert_eq!(serde_json::from_str::<Process>(json)?, process);
+        }
+        assert!(serde_json::from_str::<Process>(r#""client""#).is_err());
+        assert!(serde_json::from_str::<Process>("-1").is_err());
+
+        let json = serde_json::to_value(item(0, HistoryType::Invoke))?;
+        assert_eq!(json["process"], serde_json::json!(0));
+
+        let mixed = r#"[
+            {"index":0,"type":"invoke","f":"r","value":["r",1,null],"time":0,"process":3,"error":null},
+            {"index":1,"type":"info","f":"txn","value":[],"time":1,"process":":nemesis","error":null}
+        ]"#;
+        let his: SerializableHistoryList = serde_json::from_str(mixed)?;
+        assert_eq!(his[0].process, Process::Client(3));
+        assert_eq!(his[1].process, Process::Nemesis);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_only_marker() -> anyhow::Result<()> {
+        let json = serde_json::to_value(item(0, HistoryType::Invoke))?;
+        assert_eq!(json["read-only?"], serde_json::Value::Bool(true));
+
+        let mut his = HistoryBuilder::new()
+            .invoke(0, Op::Read(1, None))
+            .invoke(1, Op::Write(1, 1))
+            .invoke(Process::Nemesis, Op::Txn(vec![]))
+            .build();
+        let plain = serde_json::to_string(&his)?;
+        assert!(!plain.contains("read-only?"), "{plain}");
+        his.mark_read_only();
+        let marked = his.iter().map(|item| item.read_only).collect::<Vec<_>>();
+        assert_eq!(marked, vec![true, false, false]);
+
+        let mut write = item(1, HistoryType::Invoke);
+        write.read_only = false;
+        let json = serde_json::to_value(&write)?;
+        assert!(json.get("read-only?").is_none());
+        let res: SerializableHistory = serde_json::from_value(json)?;
+        assert!(!res.read_only);
+        Ok(())
+    }
 }
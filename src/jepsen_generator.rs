@@ -0,0 +1,111 @@
+//! Thin wrappers around `jepsen.generator`, so that generator timing and
+//! structure can be composed on the Clojure side before bridging the result
+//! into a Rust `RawGenerator`.
+
+use std::time::Duration;
+
+use j4rs::{errors::Result, InvocationArg};
+
+use crate::{with_jvm, CljNs, ClojureValue, CLOJURE};
+
+/// Require the `jepsen.generator` namespace
+fn ns() -> Result<CljNs> {
+    CLOJURE.require("jepsen.generator")
+}
+
+/// Wrap `gen` so that ops are emitted roughly every `dt`, see
+/// `jepsen.generator/stagger`.
+pub fn stagger(dt: Duration, gen: ClojureValue) -> Result<ClojureValue> {
+    with_jvm(|_| nsinvoke!(ns()?, "stagger", dt.as_secs_f64(), gen))
+}
+
+/// Run each generator in `gens` one after another, see
+/// `jepsen.generator/phases`.
+pub fn phases(gens: Vec<ClojureValue>) -> Result<ClojureValue> {
+    with_jvm(|_| {
+        let args = gens
+            .into_iter()
+            .map(InvocationArg::from)
+            .collect::<Vec<_>>();
+        ns()?.var("phases")?.invoke(&args)
+    })
+}
+
+/// Repeat `gen` forever, see `jepsen.generator/cycle`.
+pub fn cycle(gen: ClojureValue) -> Result<ClojureValue> {
+    with_jvm(|_| nsinvoke!(ns()?, "cycle", gen))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        init_jvm, keyword, read_edn,
+        utils::{clj_to_string, ToDe},
+        IFn,
+    };
+
+    /// Clojure fn realizing up to `n` ops of a jepsen generator on a single
+    /// thread, moving the context clock to each op's time.
+    const TAKE_OPS_FN: &str = r#"
+(require 'jepsen.generator 'jepsen.generator.context)
+(fn [gen n]
+  (loop [gen gen
+         ctx (jepsen.generator.context/context {:concurrency 1, :nodes ["n1"]})
+         ops []]
+    (if (= n (count ops))
+      ops
+      (let [[op gen'] (jepsen.generator/op gen {} ctx)]
+        (if (or (nil? op) (= :pending op))
+          ops
+          (recur gen' (assoc ctx :time (:time op)) (conj ops op)))))))
+"#;
+
+    fn take_ops(gen: ClojureValue, n: i64) -> Result<ClojureValue> {
+        IFn::new(read_edn(TAKE_OPS_FN)?)
+            .invoke(&[InvocationArg::from(gen), InvocationArg::try_from(n)?])
+    }
+
+    /// The `field` of each op, e.g. `[:write :read]` for `:f`
+    fn fields(ops: &ClojureValue, field: &str) -> Result<ClojureValue> {
+        cljinvoke!("mapv", keyword(field)?, ops.try_clone()?)
+    }
+
+    #[test]
+    fn test_phases() -> anyhow::Result<()> {
+        init_jvm();
+        let writes = cljeval!([{:f :write, :value 1} {:f :write, :value 2}])?;
+        let read = cljeval!({:f :read})?;
+        let ops = take_ops(phases(vec![writes, read])?, 10)?;
+        assert_eq!(clj_to_string(fields(&ops, "f")?)?, "[:write :write :read]");
+        assert_eq!(clj_to_string(fields(&ops, "value")?)?, "[1 2 nil]");
+        Ok(())
+    }
+
+    #[test]
+    fn test_cycle() -> anyhow::Result<()> {
+        init_jvm();
+        let gen = cycle(cljeval!([{:f :write} {:f :read}])?)?;
+        let ops = take_ops(gen, 5)?;
+        assert_eq!(
+            clj_to_string(fields(&ops, "f")?)?,
+            "[:write :read :write :read :write]"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_stagger() -> anyhow::Result<()> {
+        init_jvm();
+        let gen = stagger(Duration::from_secs(1), cycle(cljeval!({:f :read})?)?)?;
+        let ops = take_ops(gen, 5)?;
+        assert_eq!(
+            clj_to_string(fields(&ops, "f")?)?,
+            "[:read :read :read :read :read]"
+        );
+        let times: Vec<i64> = fields(&ops, "time")?.to_de()?;
+        assert!(times.windows(2).all(|w| w[0] <= w[1]), "{times:?}");
+        assert!(times[4] > times[0], "{times:?}");
+        Ok(())
+    }
+}
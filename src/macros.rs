@@ -19,7 +19,7 @@ macro_rules! cljinvoke {
         $crate::CLOJURE.var($name).invoke0()
     };
     ($name:expr, $($args:expr),*) => {
-        || -> j4rs::errors::Result<j4rs::Instance> {
+        || -> j4rs::errors::Result<$crate::ClojureValue> {
             $crate::CLOJURE.var($name)?.invoke(&[$(j4rs::InvocationArg::try_from($args)?),*])
         } ()
     };
@@ -63,12 +63,12 @@ macro_rules! cljinvoke_java_api {
 #[macro_export]
 macro_rules! nsinvoke {
     ($ns:expr, $var:expr) => {
-        || -> j4rs::errors::Result<j4rs::Instance> {
+        || -> j4rs::errors::Result<$crate::ClojureValue> {
             $ns.var($var)?.invoke(&[] as &[j4rs::InvocationArg])
         } ()
     };
     ($ns:expr, $var:expr, $($args:expr),*) => {
-        || -> j4rs::errors::Result<j4rs::Instance> {
+        || -> j4rs::errors::Result<$crate::ClojureValue> {
             $ns.var($var)?.invoke(&[$(j4rs::InvocationArg::try_from($args)?),*])
         } ()
     };
@@ -94,7 +94,7 @@ macro_rules! nseval {
 macro_rules! nsevalstr {
     ($ns:expr, $str:expr) => {
         todo!("There still exists a bug in this macro, so it is **not recommended** to use it.")
-        || -> j4rs::errors::Result<j4rs::Instance> {
+        || -> j4rs::errors::Result<$crate::ClojureValue> {
             let s = $str;
             let first_space_pos = s.find(' ').unwrap_or(s.len());
             let (x, y) = s.split_at(first_space_pos);
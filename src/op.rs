@@ -1,4 +1,5 @@
 use std::{
+    borrow::Cow,
     fmt,
     ops::{Deref, DerefMut},
 };
@@ -18,14 +19,87 @@ pub enum Op {
     Txn(Vec<Op>),
 }
 
-/// Op type of functions that being applied to db
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Op {
+    /// Get the key of a single-key op. Returns `None` if it is a txn that
+    /// touches zero or more than one distinct key.
+    pub fn key(&self) -> Option<u64> {
+        match self {
+            Op::Read(key, _) | Op::Write(key, _) => Some(*key),
+            Op::Txn(_) => {
+                let keys = self.keys();
+                (keys.len() == 1).then(|| keys[0])
+            }
+        }
+    }
+
+    /// Get the distinct keys touched by the op, recursing into txns. Keys are
+    /// sorted, and a key touched more than once appears once.
+    pub fn keys(&self) -> Vec<u64> {
+        let mut keys = vec![];
+        self.collect_keys(&mut keys);
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+
+    fn collect_keys(&self, keys: &mut Vec<u64>) {
+        match self {
+            Op::Read(key, _) | Op::Write(key, _) => keys.push(*key),
+            Op::Txn(ops) => ops.iter().for_each(|op| op.collect_keys(keys)),
+        }
+    }
+
+    /// Whether the op is a [`Op::Read`]
+    pub fn is_read(&self) -> bool {
+        matches!(self, Op::Read(_, _))
+    }
+
+    /// Whether the op is a [`Op::Write`]
+    pub fn is_write(&self) -> bool {
+        matches!(self, Op::Write(_, _))
+    }
+
+    /// Whether the op is a [`Op::Txn`]
+    pub fn is_txn(&self) -> bool {
+        matches!(self, Op::Txn(_))
+    }
+
+    /// Whether the op only reads, i.e. it is a read or a txn of reads only
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Op::Read(_, _) => true,
+            Op::Write(_, _) => false,
+            Op::Txn(ops) => ops.iter().all(Op::is_read_only),
+        }
+    }
+
+    /// Split a txn into its ops. Txns nested directly inside are flattened
+    /// one level, so `[a [b c] d]` becomes `a b c d`. A non-txn op becomes a
+    /// single-element [`Ops`].
+    pub fn flatten_txn(self) -> Ops {
+        match self {
+            Op::Txn(ops) => ops
+                .into_iter()
+                .flat_map(|op| match op {
+                    Op::Txn(inner) => inner,
+                    op => vec![op],
+                })
+                .collect(),
+            op => Ops(vec![op]),
+        }
+    }
+}
+
+/// Op type of functions that being applied to db. Defaults to `Txn`, the
+/// `f` of the empty txn standing in for a nemesis op.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum OpFunctionType {
     #[serde(rename = "r")]
     Read,
     #[serde(rename = "w")]
     Write,
+    #[default]
     Txn,
 }
 
@@ -56,49 +130,165 @@ impl DerefMut for Ops {
     }
 }
 
+impl From<Vec<Op>> for Ops {
+    fn from(ops: Vec<Op>) -> Self {
+        Self(ops)
+    }
+}
+
+impl From<&[Op]> for Ops {
+    fn from(ops: &[Op]) -> Self {
+        Self(ops.to_vec())
+    }
+}
+
+impl FromIterator<Op> for Ops {
+    fn from_iter<I: IntoIterator<Item = Op>>(iter: I) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
 impl Ops {
     /// Reverse the order of the ops
     pub fn rev(self) -> Self {
         Self(self.0.into_iter().rev().collect())
     }
+
+    /// Combine all the ops into one [`Op::Txn`]
+    pub fn into_single_txn(self) -> Op {
+        Op::Txn(self.0)
+    }
 }
 
 // Serialize and Deserialize
 
-/// Parse an [`Op`] from JSON
-fn parse_op(json: &Value) -> Result<Op> {
-    match json {
-        Value::Array(arr) => {
-            // If the first value is a string, it must not be a Txn, whose first element is
-            // Vec
-            if let Some(op_type) = arr[0].as_str() {
-                // Handle Read or Write
-                let key = arr[1].as_u64().ok_or(anyhow!("Invalid key"))?;
-                let value = arr[2].as_u64();
-                match op_type {
-                    "r" => Ok(Op::Read(key, value)),
-                    "w" => Ok(Op::Write(key, value.ok_or(anyhow!("Invalid value"))?)),
-                    _ => Err(anyhow!("Unknown op type")),
+/// How a read without a value is serialized by [`OpFormat`]. Apply it to a
+/// whole history with
+/// [`SerializableHistoryList::to_json_with`](crate::history::SerializableHistoryList::to_json_with).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoneRead {
+    /// `["r", k, null]`
+    #[default]
+    Nil,
+    /// `["r", k]`, as some Elle variants expect for read invokes
+    Absent,
+}
+
+/// The function tags used by [`OpFormat`], e.g. `get`/`set` for a store with
+/// its own naming. Txns are serialized as plain arrays of micro-ops, so the
+/// `txn` tag only shows up as the `f` of history items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpTags {
+    pub read: Cow<'static, str>,
+    pub write: Cow<'static, str>,
+    pub txn: Cow<'static, str>,
+}
+
+impl Default for OpTags {
+    fn default() -> Self {
+        Self {
+            read: Cow::Borrowed("r"),
+            write: Cow::Borrowed("w"),
+            txn: Cow::Borrowed("txn"),
+        }
+    }
+}
+
+/// The serialization format of [`Op`]s. The default is what the `Serialize`
+/// impl of [`Op`] emits; parsing accepts reads both with and without the
+/// value position regardless of the format.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct OpFormat {
+    pub none_read: NoneRead,
+    pub tags: OpTags,
+}
+
+impl OpFormat {
+    /// Set how reads without a value are serialized
+    pub fn with_none_read(mut self, none_read: NoneRead) -> Self {
+        self.none_read = none_read;
+        self
+    }
+
+    /// Set the function tags of reads, writes and txns
+    pub fn with_tags(mut self, tags: OpTags) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Wrap `op` so that it serializes in this format
+    pub fn wrap<'a>(&'a self, op: &'a Op) -> FormattedOp<'a> {
+        FormattedOp { op, format: self }
+    }
+
+    /// The tag of `f` in this format, e.g. the `f` of a history item
+    pub fn f_tag(&self, f: &OpFunctionType) -> &str {
+        match f {
+            OpFunctionType::Read => &self.tags.read,
+            OpFunctionType::Write => &self.tags.write,
+            OpFunctionType::Txn => &self.tags.txn,
+        }
+    }
+
+    /// Convert an [`Op`] to JSON
+    pub fn to_json(&self, op: &Op) -> Value {
+        match op {
+            Op::Read(key, None) if self.none_read == NoneRead::Absent => {
+                json!([self.tags.read, key])
+            }
+            Op::Read(key, value) => json!([self.tags.read, key, value]),
+            Op::Write(key, value) => json!([self.tags.write, key, value]),
+            Op::Txn(ops) => Value::Array(ops.iter().map(|op| self.to_json(op)).collect()),
+        }
+    }
+
+    /// Parse an [`Op`] from JSON
+    pub fn parse(&self, json: &Value) -> Result<Op> {
+        match json {
+            Value::Array(arr) => {
+                // If the first value is a string, it must not be a Txn, whose first element is
+                // Vec
+                if let Some(op_type) = arr.first().and_then(Value::as_str) {
+                    // Handle Read or Write
+                    let key = arr
+                        .get(1)
+                        .and_then(Value::as_u64)
+                        .ok_or(anyhow!("Invalid key"))?;
+                    let value = arr.get(2).and_then(Value::as_u64);
+                    if op_type == self.tags.read {
+                        Ok(Op::Read(key, value))
+                    } else if op_type == self.tags.write {
+                        Ok(Op::Write(key, value.ok_or(anyhow!("Invalid value"))?))
+                    } else {
+                        Err(anyhow!("Unknown op type"))
+                    }
+                } else {
+                    // Handle Txn
+                    let ops = arr
+                        .iter()
+                        .map(|op| self.parse(op))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    Ok(Op::Txn(ops))
                 }
-            } else {
-                // Handle Txn
-                let ops = arr.iter().map(parse_op).collect::<Result<Vec<_>, _>>()?;
-                Ok(Op::Txn(ops))
             }
+            _ => Err(anyhow!("Invalid JSON format")),
         }
-        _ => Err(anyhow!("Invalid JSON format")),
     }
 }
 
-/// Convert an [`Op`] to JSON
-fn op_to_json(op: &Op) -> Value {
-    match op {
-        Op::Read(key, value) => json!(["r", key, value]),
-        Op::Write(key, value) => json!(["w", key, value]),
-        Op::Txn(ops) => {
-            let json_ops: Vec<Value> = ops.iter().map(op_to_json).collect();
-            Value::Array(json_ops)
-        }
+/// An [`Op`] serializing in an [`OpFormat`], see [`OpFormat::wrap`]
+#[derive(Debug, Clone, Copy)]
+pub struct FormattedOp<'a> {
+    op: &'a Op,
+    format: &'a OpFormat,
+}
+
+impl Serialize for FormattedOp<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.format.to_json(self.op).serialize(serializer)
     }
 }
 
@@ -107,8 +297,7 @@ impl Serialize for Op {
     where
         S: serde::Serializer,
     {
-        let json_value = op_to_json(self);
-        json_value.serialize(serializer)
+        OpFormat::default().wrap(self).serialize(serializer)
     }
 }
 
@@ -127,7 +316,9 @@ impl<'de> Visitor<'de> for OpVisitor {
         while let Some(value) = seq.next_element()? {
             extract_arr.push(value);
         }
-        parse_op(&serde_json::Value::Array(extract_arr)).map_err(serde::de::Error::custom)
+        OpFormat::default()
+            .parse(&serde_json::Value::Array(extract_arr))
+            .map_err(serde::de::Error::custom)
     }
 }
 
@@ -142,10 +333,7 @@ impl<'de> Deserialize<'de> for Op {
 
 #[cfg(test)]
 mod test {
-    use j4rs::Instance;
-
     use super::*;
-    use crate::utils::{FromSerde, ToDe};
 
     #[test]
     fn test_op_serde() {
@@ -163,6 +351,129 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_op_format_none_read() -> anyhow::Result<()> {
+        let op = Op::Txn(vec![
+            Op::Read(8, None),
+            Op::Read(9, Some(1)),
+            Op::Write(6, 1),
+        ]);
+        let absent = OpFormat::default().with_none_read(NoneRead::Absent);
+        for (format, json_str) in [
+            (OpFormat::default(), r#"[["r",8,null],["r",9,1],["w",6,1]]"#),
+            (absent, r#"[["r",8],["r",9,1],["w",6,1]]"#),
+        ] {
+            assert_eq!(serde_json::to_string(&format.wrap(&op))?, json_str);
+            assert_eq!(serde_json::from_str::<Op>(json_str)?, op);
+        }
+        assert_eq!(
+            serde_json::to_string(&op)?,
+            serde_json::to_string(&OpFormat::default().wrap(&op))?
+        );
+
+        assert!(serde_json::from_str::<Op>(r#"["w",6]"#).is_err());
+        assert!(serde_json::from_str::<Op>(r#"["r"]"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_op_format_tags() -> anyhow::Result<()> {
+        let format = OpFormat::default().with_tags(OpTags {
+            read: "get".into(),
+            write: "set".into(),
+            txn: "multi".into(),
+        });
+        let op = Op::Txn(vec![Op::Write(6, 1), Op::Read(8, None)]);
+        let json = serde_json::to_value(format.wrap(&op))?;
+        assert_eq!(json, json!([["set", 6, 1], ["get", 8, null]]));
+        assert_eq!(format.f_tag(&OpFunctionType::from(&op)), "multi");
+        assert_eq!(OpFormat::default().f_tag(&OpFunctionType::Txn), "txn");
+        assert_eq!(format.parse(&json)?, op);
+        assert!(OpFormat::default().parse(&json).is_err());
+        assert!(format.parse(&json!(["r", 8, null])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_op_accessors() {
+        let read = Op::Read(1, None);
+        assert_eq!(read.key(), Some(1));
+        assert_eq!(read.keys(), vec![1]);
+        assert!(read.is_read() && !read.is_write() && !read.is_txn());
+
+        let write = Op::Write(2, 3);
+        assert_eq!(write.key(), Some(2));
+        assert!(write.is_write());
+
+        let single = Op::Txn(vec![Op::Write(4, 1), Op::Read(4, None)]);
+        assert_eq!(single.key(), Some(4));
+        assert_eq!(single.keys(), vec![4]);
+        assert!(single.is_txn());
+
+        let nested = Op::Txn(vec![
+            Op::Write(6, 1),
+            Op::Txn(vec![Op::Read(8, None), Op::Write(5, 2)]),
+        ]);
+        assert_eq!(nested.key(), None);
+        assert_eq!(nested.keys(), vec![5, 6, 8]);
+
+        assert_eq!(Op::Txn(vec![]).key(), None);
+    }
+
+    #[test]
+    fn test_op_is_read_only() {
+        assert!(Op::Read(1, None).is_read_only());
+        assert!(!Op::Write(1, 1).is_read_only());
+        assert!(Op::Txn(vec![Op::Read(1, None), Op::Read(2, Some(1))]).is_read_only());
+        assert!(!Op::Txn(vec![Op::Write(1, 1), Op::Write(2, 1)]).is_read_only());
+        assert!(!Op::Txn(vec![Op::Read(1, None), Op::Write(2, 1)]).is_read_only());
+        assert!(!Op::Txn(vec![Op::Read(1, None), Op::Txn(vec![Op::Write(2, 1)])]).is_read_only());
+    }
+
+    #[test]
+    fn test_single_txn_conversion() {
+        let ops = Ops(vec![Op::Write(1, 1), Op::Read(2, None)]);
+        let txn = ops.clone().into_single_txn();
+        assert_eq!(txn, Op::Txn(vec![Op::Write(1, 1), Op::Read(2, None)]));
+        assert_eq!(txn.flatten_txn(), ops);
+
+        assert_eq!(Op::Write(1, 1).flatten_txn(), Ops(vec![Op::Write(1, 1)]));
+
+        let nested = Op::Txn(vec![
+            Op::Write(1, 1),
+            Op::Txn(vec![
+                Op::Read(2, None),
+                Op::Txn(vec![Op::Write(3, 1), Op::Write(4, 1)]),
+            ]),
+            Op::Read(5, None),
+        ]);
+        assert_eq!(
+            nested.flatten_txn(),
+            Ops(vec![
+                Op::Write(1, 1),
+                Op::Read(2, None),
+                Op::Txn(vec![Op::Write(3, 1), Op::Write(4, 1)]),
+                Op::Read(5, None),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_ops_conversions() {
+        let vec = vec![Op::Write(1, 1), Op::Read(2, None)];
+        let expected = Ops(vec.clone());
+
+        let from_slice: Ops = vec.as_slice().into();
+        assert_eq!(from_slice, expected);
+        let from_vec: Ops = vec.clone().into();
+        assert_eq!(from_vec, expected);
+        let collected: Ops = vec.into_iter().collect();
+        assert_eq!(collected, expected);
+
+        let empty: Ops = std::iter::empty().collect();
+        assert!(empty.is_empty());
+    }
+
     #[test]
     fn test_ops_serde() {
         let json_str = r#"
@@ -181,14 +492,19 @@ mod test {
         assert_eq!(serde_json::from_str::<Ops>(json_str).unwrap(), ops);
     }
 
+    #[cfg(feature = "jvm")]
     #[test]
     fn test_convertion_between_ops_and_instance() {
+        use crate::ClojureValue;
+
+        use crate::utils::{FromSerde, ToDe};
+
         let ops = Ops(vec![
             Op::Txn(vec![Op::Write(6, 1), Op::Write(8, 1)]),
             Op::Txn(vec![Op::Write(9, 1), Op::Read(8, None)]),
         ]);
 
-        let inst: Instance = Instance::from_ser(ops.clone()).unwrap();
+        let inst = ClojureValue::from_ser(ops.clone()).unwrap();
         let res: Ops = inst.to_de().unwrap();
         assert_eq!(ops, res);
     }
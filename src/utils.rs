@@ -1,8 +1,8 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use j4rs::{errors::Result as jResult, Instance, InvocationArg};
 use serde::Serialize;
 
-use crate::{cljeval, cljinvoke, nsinvoke, with_jvm, CLOJURE};
+use crate::{read_edn, with_jvm, ClojureValue, IFn, CLOJURE};
 
 /// print a java instance
 pub fn print(inst: Instance) {
@@ -14,8 +14,8 @@ pub fn print(inst: Instance) {
     })
 }
 
-/// print a clojure instance
-pub fn print_clj(inst: Instance) {
+/// print a clojure value
+pub fn print_clj(inst: ClojureValue) {
     println!("{}", clj_to_string(inst).die());
 }
 
@@ -27,8 +27,19 @@ pub fn print_clj(inst: Instance) {
 /// let res = clj_to_string(cljeval!((assoc {:a 1} :b "hello")).unwrap()).unwrap();
 /// assert_eq!(res, "{:a 1, :b \"hello\"}".to_string());
 /// ```
-pub fn clj_to_string(inst: Instance) -> jResult<String> {
-    with_jvm(|_| java_to_string(&cljinvoke!("pr-str", inst)?))
+pub fn clj_to_string(inst: ClojureValue) -> jResult<String> {
+    with_jvm(|_| java_to_string(cljinvoke!("pr-str", inst)?.as_instance()))
+}
+
+/// Convert a Clojure instance to a pretty printed, multi-line EDN string via
+/// `clojure.pprint/pprint`. Useful for inspecting large histories.
+pub fn clj_to_string_pretty(inst: ClojureValue) -> jResult<String> {
+    with_jvm(|jvm| {
+        let pprint = CLOJURE.require("clojure.pprint")?;
+        let writer = jvm.create_instance("java.io.StringWriter", InvocationArg::empty())?;
+        nsinvoke!(pprint, "pprint", inst, jvm.clone_instance(&writer)?)?;
+        java_to_string(&jvm.invoke(&writer, "toString", InvocationArg::empty())?)
+    })
 }
 
 /// Convert a java instance `j4rs::Instance` to a rust String
@@ -55,26 +66,65 @@ impl<T> J4rsDie<T> for j4rs::errors::Result<T> {
 
 /// This fn is to extract the value of generated ops from elle generator.
 /// This function should be called before serialize the Instance.
-pub fn pre_serialize(i: Instance) -> jResult<Instance> {
-    cljinvoke!("map", cljeval!(#(:value %)), i)
+pub fn pre_serialize(i: ClojureValue) -> jResult<ClojureValue> {
+    cljinvoke!("map", cljeval!(#(:value %))?, i)
 }
 
 /// Convert a clojure instance to json string
-pub fn clj_jsonify(inst: Instance) -> jResult<String> {
+pub fn clj_jsonify(inst: ClojureValue) -> jResult<String> {
     with_jvm(|_| {
         let json = CLOJURE.require("clojure.data.json")?;
-        java_to_string(&nsinvoke!(json, "write-str", inst)?)
+        java_to_string(nsinvoke!(json, "write-str", inst)?.as_instance())
     })
 }
 
 /// Convert a json string to clojure instance
-pub fn clj_from_json(s: &str) -> jResult<Instance> {
+pub fn clj_from_json(s: &str) -> jResult<ClojureValue> {
     with_jvm(|_| {
         let json = CLOJURE.require("clojure.data.json")?;
         nsinvoke!(json, "read-str", s)
     })
 }
 
+/// Clojure fn returning the path of the first difference between two values,
+/// or nil if they are equal.
+const FIRST_DIFF_FN: &str = r#"
+(fn first-diff [a b path]
+  (cond
+    (= a b) nil
+    (and (map? a) (map? b))
+    (some (fn [k] (first-diff (get a k) (get b k) (conj path k)))
+          (distinct (concat (keys a) (keys b))))
+    (and (sequential? a) (sequential? b) (= (count a) (count b)))
+    (some (fn [i] (first-diff (nth a i) (nth b i) (conj path i)))
+          (range (count a)))
+    :else path))
+"#;
+
+/// Check that a clojure instance survives the json round trip through
+/// [`clj_jsonify`] and [`clj_from_json`]. Keywords are known to lose their `:`
+/// on the way, so this is a debugging aid for new op shapes. Returns an error
+/// with the path of the first difference.
+pub fn assert_roundtrip(inst: ClojureValue) -> Result<()> {
+    with_jvm(|_| {
+        let back = clj_from_json(&clj_jsonify(inst.try_clone()?)?)?;
+        let first_diff = IFn::new(read_edn(FIRST_DIFF_FN)?);
+        let path = first_diff.invoke(&[
+            InvocationArg::from(inst),
+            InvocationArg::from(back),
+            InvocationArg::from(cljeval!([])?),
+        ])?;
+        let path = clj_to_string(path)?;
+        if path == "nil" {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "value changed after json round trip at path {path}"
+            ))
+        }
+    })
+}
+
 /// Convert any rust struct which impl Serialize to clojure instance
 pub trait FromSerde {
     fn from_ser<T: Serialize>(s: T) -> Result<Self>
@@ -82,7 +132,7 @@ pub trait FromSerde {
         Self: Sized;
 }
 
-impl FromSerde for Instance {
+impl FromSerde for ClojureValue {
     fn from_ser<T: Serialize>(s: T) -> Result<Self>
     where
         Self: Sized,
@@ -91,17 +141,32 @@ impl FromSerde for Instance {
     }
 }
 
+impl FromSerde for Instance {
+    fn from_ser<T: Serialize>(s: T) -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Ok(ClojureValue::from_ser(s)?.into_instance())
+    }
+}
+
 /// Convert clojure instance to any rust struct which impl Serialize
 pub trait ToDe {
     fn to_de<T: for<'de> serde::Deserialize<'de>>(self) -> Result<T>;
 }
 
-impl ToDe for Instance {
+impl ToDe for ClojureValue {
     fn to_de<T: for<'de> serde::Deserialize<'de>>(self) -> Result<T> {
         Ok(serde_json::from_str(&clj_jsonify(self)?)?)
     }
 }
 
+impl ToDe for Instance {
+    fn to_de<T: for<'de> serde::Deserialize<'de>>(self) -> Result<T> {
+        ClojureValue::new(self).to_de()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use serde::Deserialize;
@@ -132,7 +197,30 @@ mod tests {
             a: 1,
             b: "hello".to_string(),
         };
-        let res: Instance = Instance::from_ser(&s).unwrap();
+        let res = ClojureValue::from_ser(&s).unwrap();
         print_clj(res);
     }
+
+    #[test]
+    fn test_assert_roundtrip() -> anyhow::Result<()> {
+        init_jvm();
+        assert_roundtrip(read_edn(r#"{"a" [1 {"b" "c"}]}"#)?)?;
+        let err = assert_roundtrip(read_edn(r#"{"a" [1 {"b" :c}]}"#)?).unwrap_err();
+        assert!(err.to_string().contains(r#"["a" 1 "b"]"#), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_clj_to_string_pretty() -> anyhow::Result<()> {
+        init_jvm();
+        let edn = r#"{:valid? false, :anomaly-types [:G1c :G-single-item],
+            :anomalies {:G1c [{:cycle [{:index 0, :value [[:w 1 1] [:r 2 1]]}
+                                       {:index 1, :value [[:w 2 1] [:r 1 1]]}]}]},
+            :not #{:read-committed}}"#;
+        let pretty = clj_to_string_pretty(read_edn(edn)?)?;
+        assert!(pretty.trim().lines().count() > 1);
+        let eq = cljinvoke!("=", read_edn(&pretty)?, read_edn(edn)?)?;
+        assert_eq!(clj_to_string(eq)?, "true");
+        Ok(())
+    }
 }